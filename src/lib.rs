@@ -1,36 +1,92 @@
 #![no_std]
 
 pub mod wallet;
-use wallet::{uint, MultiSigWallet};
+use wallet::{uint, MultiSigWallet, TransactionInfo, GasReservationInfo, ConfirmationRecord, ProposalDetails, NewTransaction, LogEntry, ArchivedTransaction, Delegation, AllowanceInfo, QuorumMode, OwnerInvite, Condition, ExecutionStage, ExecutionResult};
 use gstd::{msg, prelude::*, ActorId};
 
+// New variants go at the end, the index of a variant is part of its encoding.
 #[derive(Debug, Decode, Encode, TypeInfo)]
 pub enum Action {
     AddOwner(ActorId),
     RemoveOwner(ActorId),
     ReplaceOwner{from: ActorId, to: ActorId},
     ChangeRequirement{required:uint},
-    SubmitTransaction{destination:ActorId, value:uint, data:Vec<u8>},
-    ConfirmTransaction{transaction_id:uint},
-    RevokeConfirmation{transaction_id:uint},
+    SubmitTransaction{destination:ActorId, value:uint, data:Vec<u8>, gas_limit:Option<u64>, details:ProposalDetails, depends_on:Vec<uint>, nonce:Option<u64>, condition:Option<Condition>},
+    ConfirmTransaction{transaction_id:uint, comment:Option<String>},
+    RevokeConfirmation{transaction_id:uint, comment:Option<String>},
     ExecuteTransaction{transaction_id:uint},
     IsConfirmed{transaction_id:uint},
     GetConfirmationCount{transaction_id:uint},
     GetTransactionCount{pending:bool, executed:bool},
     GetOwners,
     GetConfirmations{transaction_id:uint},
-    GetTransactionIds{from:uint, to:uint, pending:bool, executed:bool}
+    GetTransactionIds{from_id:Option<uint>, limit:u32, reverse:bool, pending:bool, executed:bool},
+    ChangeGasLimit{transaction_id:uint, gas_limit:Option<u64>},
+    GetTransaction{transaction_id:uint},
+    ExecuteScheduled{transaction_id:uint},
+    ReserveGas{amount:u64, duration:u32},
+    ReleaseGas,
+    GetGasReservations,
+    RetryTransaction{transaction_id:uint},
+    ChangeMaxRetries{max_retries:u32},
+    GetConfirmationDetails{transaction_id:uint},
+    AmendDescription{transaction_id:uint, description:Option<String>},
+    GetEvents{from_seq:u64, limit:u32},
+    PruneTransactions{before_id:uint},
+    GetArchive{from_index:u32, limit:u32},
+    RejectTransaction{transaction_id:uint, comment:Option<String>},
+    GetVotes{transaction_id:uint},
+    DelegateTo{delegate:ActorId, until_block:u32},
+    Undelegate,
+    GetDelegations,
+    ConfirmOnWallet{wallet:ActorId, transaction_id:uint},
+    RevokeOnWallet{wallet:ActorId, transaction_id:uint},
+    RemoveChildWallet{wallet:ActorId},
+    SetAllowance{owner:ActorId, amount:uint, period:u32, destination:Option<ActorId>},
+    RemoveAllowance{owner:ActorId},
+    SpendAllowance{to:ActorId, value:uint},
+    GetAllowance{owner:ActorId},
+    ChangeQuorumMode{mode:QuorumMode},
+    GetQuorum,
+    SetGuard{guard:Option<ActorId>},
+    GetGuard,
+    EnableModule{module:ActorId},
+    DisableModule{module:ActorId},
+    ExecFromModule{destination:ActorId, value:uint, data:Vec<u8>},
+    GetModules{from:Option<ActorId>, limit:u32},
+    AddListener{listener:ActorId, filter:NotificationFilter},
+    RemoveListener{listener:ActorId},
+    GetListeners,
+    Pause,
+    Unpause,
+    ChangePauseConfig{threshold:uint, max_duration:u32},
+    GetPauseState,
+    AcceptOwnership,
+    GetInvites,
+    ChangeSnapshotMode{enabled:bool},
+    GetOwnerSetVersion,
+    CancelTransaction{transaction_id:uint},
+    ExpireTransaction{transaction_id:uint},
+    ChangeBondConfig{bond:uint, ttl:u32},
+    GetBondConfig,
+    ChangeNonceMode{enabled:bool},
+    GetNonce,
+    ClaimBond{transaction_id:uint},
+    GetExecutions,
+    AbortExecution{transaction_id:uint},
 }
 
 
-#[derive(Debug, Encode, Decode, TypeInfo)]
+// New variants go at the end, the index of a variant is part of its encoding.
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
 pub enum Event {
     Invalid,
-    Confirmation {sender: ActorId, transaction_id: uint, executed: bool},
+    /// `dispatched` is true if the confirmation started the execution, its outcome follows as `Execution` or `ExecutionFailure`.
+    Confirmation {sender: ActorId, transaction_id: uint, dispatched: bool},
     Revocation{sender:ActorId, transaction_id:uint},
-    Submission{transaction_id:uint},
+    Submission{transaction_id:uint, details:ProposalDetails},
     Execution{transaction_id:uint},
-    ExecutionFailure{transaction_id:uint},
+    ExecutionFailure{transaction_id:uint, reason:String},
     IsConfirmed(bool),
     GetConfirmationCount(uint),
     GetTransactionCount(uint),
@@ -40,8 +96,126 @@ pub enum Event {
     OwnerReplace{from:ActorId, to:ActorId},
     RequirementChange{from: uint, to: uint},
     GetConfirmations(Vec<ActorId>),
-    GetTransactionIds(Vec<uint>),
+    GetTransactionIds{ids:Vec<uint>, next_cursor:Option<uint>},
     GetOwners(Vec<ActorId>),
+    GasLimitChange{transaction_id:uint, gas_limit:Option<u64>},
+    GetTransaction(TransactionInfo),
+    GasReservation{sender:ActorId, amount:u64, duration:u32},
+    GasRelease{amount:u64},
+    GetGasReservations{total:u64, reservations:Vec<GasReservationInfo>},
+    MaxRetriesChange{max_retries:u32},
+    GetConfirmationDetails(Vec<ConfirmationRecord>),
+    DescriptionAmendment{transaction_id:uint},
+    GetEvents(Vec<LogEntry>),
+    TransactionsPruned{before_id:uint, count:u32, archive_head:[u8; 32]},
+    GetArchive{entries:Vec<ArchivedTransaction>, head:[u8; 32]},
+    Rejection{sender:ActorId, transaction_id:uint, cancelled:bool},
+    Cancellation{transaction_id:uint},
+    GetVotes{approve:Vec<ActorId>, reject:Vec<ActorId>, abstain:Vec<ActorId>},
+    Delegation{owner:ActorId, delegate:ActorId, until_block:u32},
+    Undelegation{owner:ActorId},
+    GetDelegations(Vec<Delegation>),
+    ChildWalletRemoval{wallet:ActorId},
+    AllowanceChange{owner:ActorId, amount:uint, period:u32, destination:Option<ActorId>},
+    AllowanceRemoval{owner:ActorId},
+    AllowanceSpent{owner:ActorId, to:ActorId, value:uint, remaining:uint},
+    GetAllowance(Option<AllowanceInfo>),
+    QuorumModeChange{mode: QuorumMode},
+    GetQuorum{mode:QuorumMode, required:uint},
+    GuardChange{guard:Option<ActorId>},
+    GuardRejection{transaction_id:uint, reason:String},
+    GetGuard(Option<ActorId>),
+    ModuleEnabled{module:ActorId},
+    ModuleDisabled{module:ActorId},
+    ExecutionFromModule{module:ActorId, destination:ActorId, value:uint, failure:Option<String>},
+    GetModules{modules:Vec<ActorId>, next:Option<ActorId>},
+    ListenerAdded{listener:ActorId, filter:NotificationFilter},
+    ListenerRemoved{listener:ActorId},
+    GetListeners(Vec<(ActorId, NotificationFilter)>),
+    PauseVote{owner:ActorId, paused:bool},
+    Unpause,
+    PauseConfigChange{threshold:uint, max_duration:u32},
+    GetPauseState{paused_until:Option<u32>, votes:Vec<ActorId>, threshold:uint, max_duration:u32},
+    OwnerInvitation{invitee:ActorId, replaces:Option<ActorId>, expires_at:u32},
+    GetInvites(Vec<OwnerInvite>),
+    SnapshotModeChange{enabled:bool},
+    GetOwnerSetVersion{version:u32, snapshot_mode:bool},
+    BondConfigChange{bond:uint, ttl:u32},
+    BondRefund{transaction_id:uint, submitter:ActorId, amount:uint},
+    BondForfeit{transaction_id:uint, amount:uint},
+    GetBondConfig{bond:uint, ttl:u32},
+    NonceModeChange{enabled:bool},
+    GetNonce{next_nonce:u64, strict:bool},
+    ConditionNotMet{transaction_id:uint, reason:String},
+    BondRefundFailure{transaction_id:uint, amount:uint, reason:String},
+    ExecutionDispatched{transaction_id:uint},
+    ModuleCallDispatched{call_id:uint},
+    GetExecutions(Vec<(uint, ExecutionStage)>),
+    ExecutionAborted{transaction_id:uint},
+    AllowanceSpendDispatched{owner:ActorId, to:ActorId, value:uint},
+    AllowanceSpendRejection{owner:ActorId, to:ActorId, value:uint, reason:String},
+}
+
+/// Sent by the wallet to its guard program, which answers with a `GuardReply`.
+#[derive(Debug, Decode, Encode, TypeInfo)]
+pub enum GuardAction {
+    CheckTransaction{transaction_id:uint, destination:ActorId, value:uint, data:Vec<u8>, gas_limit:Option<u64>},
+    CheckAfterExecution{transaction_id:uint, success:bool},
+    CheckModuleCall{module:ActorId, destination:ActorId, value:uint, data:Vec<u8>},
+    CheckAllowanceSpend{owner:ActorId, to:ActorId, value:uint},
+}
+
+#[derive(Debug, Decode, Encode, TypeInfo)]
+pub enum GuardReply {
+    Approved,
+    Rejected{reason:String},
+}
+
+/// Sent by the wallet to its listener programs, no reply is expected.
+#[derive(Debug, Clone, Decode, Encode, TypeInfo)]
+pub enum Notification {
+    Submission{transaction_id:uint},
+    Confirmation{owner:ActorId, transaction_id:uint},
+    Execution{transaction_id:uint},
+    ExecutionFailure{transaction_id:uint, reason:String},
+    OwnerChange{added:Option<ActorId>, removed:Option<ActorId>},
+}
+
+impl Notification {
+    fn from_event(event: &Event) -> Option<Self> {
+        match event {
+            Event::Submission{transaction_id, ..} => Some(Notification::Submission{transaction_id:*transaction_id}),
+            Event::Confirmation{sender, transaction_id, ..} => Some(Notification::Confirmation{owner:*sender, transaction_id:*transaction_id}),
+            Event::Execution{transaction_id, ..} => Some(Notification::Execution{transaction_id:*transaction_id}),
+            Event::ExecutionFailure{transaction_id, reason, ..} => Some(Notification::ExecutionFailure{transaction_id:*transaction_id, reason:reason.clone()}),
+            Event::OwnerAddition{owner} => Some(Notification::OwnerChange{added:Some(*owner), removed:None}),
+            Event::OwnerRemoval{owner} => Some(Notification::OwnerChange{added:None, removed:Some(*owner)}),
+            Event::OwnerReplace{from, to} => Some(Notification::OwnerChange{added:Some(*to), removed:Some(*from)}),
+            _ => None,
+        }
+    }
+}
+
+/// Notifications a listener program subscribes to.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Decode, Encode, TypeInfo)]
+pub struct NotificationFilter {
+    pub submission: bool,
+    pub confirmation: bool,
+    pub execution: bool,
+    pub execution_failure: bool,
+    pub owner_change: bool,
+}
+
+impl NotificationFilter {
+    fn accepts(&self, notification: &Notification) -> bool {
+        match notification {
+            Notification::Submission{..} => self.submission,
+            Notification::Confirmation{..} => self.confirmation,
+            Notification::Execution{..} => self.execution,
+            Notification::ExecutionFailure{..} => self.execution_failure,
+            Notification::OwnerChange{..} => self.owner_change,
+        }
+    }
 }
 
 #[derive(Debug, Decode, Encode, TypeInfo)]
@@ -68,53 +242,264 @@ gstd::metadata! {
         output: Event,
 }
 
-#[gstd::async_main]
-async unsafe fn main() {
+// Replies with a state-changing event and appends it to the audit log.
+fn reply_logged(wallet: &mut MultiSigWallet, event: Event) {
+    wallet.log_event(&msg::source(), event.clone());
+    msg::reply(event, 0);
+}
+
+// Execution events are already logged by the wallet itself.
+fn reply_execution(wallet: &MultiSigWallet, transaction_id: uint, result: ExecutionResult) {
+    let transaction = wallet.get_transaction(&transaction_id);
+    match result {
+         ExecutionResult::Executed => {msg::reply(Event::Execution{transaction_id}, 0);},
+         ExecutionResult::Failed => {
+             let reason = transaction.last_failure.unwrap_or_default();
+             msg::reply(Event::ExecutionFailure{transaction_id, reason}, 0);
+         },
+         ExecutionResult::GuardRejected => {
+             let reason = transaction.last_failure.unwrap_or_default();
+             msg::reply(Event::GuardRejection{transaction_id, reason}, 0);
+         },
+         ExecutionResult::ConditionNotMet => {
+             let reason = transaction.last_failure.unwrap_or_default();
+             msg::reply(Event::ConditionNotMet{transaction_id, reason}, 0);
+         },
+         // the outcome is logged once the replies arrive
+         ExecutionResult::Dispatched => {msg::reply(Event::ExecutionDispatched{transaction_id}, 0);},
+         _ => {},
+    }
+}
+
+// Executions are settled here rather than awaited, the wallet is never borrowed across a reply.
+#[no_mangle]
+pub unsafe extern "C" fn handle_reply() {
+    let wallet: &mut MultiSigWallet = unsafe {WALLET.get_or_insert(MultiSigWallet::default())};
+    let reply_to = msg::reply_to().expect("Could not get the replied message");
+    let reply = match msg::exit_code() {
+        Ok(0) => Ok(msg::load_bytes()),
+        code => Err(format!("exit code {:?}", code)),
+    };
+    wallet.handle_reply(&reply_to, reply);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn handle() {
     let action: Action = msg::load().expect("Could not load Action");
     let wallet: &mut MultiSigWallet = unsafe {WALLET.get_or_insert(MultiSigWallet::default())};
     match action {
         Action::AddOwner(owner) => {
-            wallet.add_owner(&owner);
+            let OwnerInvite{invitee, replaces, expires_at} = wallet.add_owner(&owner);
 
-            msg::reply(Event::OwnerAddition{owner}, 0);
+            reply_logged(wallet, Event::OwnerInvitation{invitee, replaces, expires_at});
         }
         Action::RemoveOwner(owner) => {
             wallet.remove_owner(&owner);
 
-            msg::reply(Event::OwnerRemoval{owner}, 0);
+            reply_logged(wallet, Event::OwnerRemoval{owner});
         }
         Action::ReplaceOwner{from, to} => {
-            wallet.replace_owner(&from, &to);
+            let OwnerInvite{invitee, replaces, expires_at} = wallet.replace_owner(&from, &to);
+
+            reply_logged(wallet, Event::OwnerInvitation{invitee, replaces, expires_at});
+        }
+        Action::AcceptOwnership => {
+            let invite = wallet.accept_ownership(&msg::source());
+            let event = match invite.replaces {
+                Some(from) => Event::OwnerReplace{from, to:invite.invitee},
+                None => Event::OwnerAddition{owner:invite.invitee},
+            };
 
-            msg::reply(Event::OwnerReplace{from,to}, 0);
+            reply_logged(wallet, event);
         }
         Action::ChangeRequirement{required} => {
-            wallet.change_requirement(required);
             let from = wallet.get_required();
+            wallet.change_requirement(required);
 
-            msg::reply(Event::RequirementChange{from, to:required}, 0);
+            reply_logged(wallet, Event::RequirementChange{from, to:required});
         }
-        Action::SubmitTransaction{destination, value, data} => {
-            let id = wallet.submit_transaction(&msg::source(), &destination, &value, &data).await;
-            msg::reply(Event::Submission{transaction_id:id}, 0);
+        Action::ChangeQuorumMode{mode} => {
+            wallet.change_quorum_mode(mode);
+
+            reply_logged(wallet, Event::QuorumModeChange{mode});
         }
-        Action::ConfirmTransaction{transaction_id} => {
-            let i = wallet.confirm_transaction(&msg::source(), &transaction_id).await;
-            msg::reply(Event::Confirmation{sender:msg::source().clone(), transaction_id: transaction_id.clone(), executed: i == 1}, 0);
+        Action::ChangeSnapshotMode{enabled} => {
+            wallet.change_snapshot_mode(enabled);
+
+            reply_logged(wallet, Event::SnapshotModeChange{enabled});
         }
-        Action::RevokeConfirmation{transaction_id} => {
-            wallet.revoke_confirmation(&msg::source(), &transaction_id);
-            msg::reply(Event::Revocation{sender:msg::source(), transaction_id:transaction_id}, 0);
+        Action::ChangeNonceMode{enabled} => {
+            wallet.change_nonce_mode(enabled);
+
+            reply_logged(wallet, Event::NonceModeChange{enabled});
         }
-        Action::ExecuteTransaction{transaction_id} => {
-            let i = wallet.execute_transaction(&msg::source(), &transaction_id).await;
-            match i {
-                 0 => {},
-                 1 => {msg::reply(Event::Execution{transaction_id: transaction_id}, 0);},
-                 2 => {msg::reply(Event::ExecutionFailure{transaction_id: transaction_id}, 0);},
-                 _ => {},
+        Action::SubmitTransaction{destination, value, data, gas_limit, details, depends_on, nonce, condition} => {
+            let txn = NewTransaction{destination, value, data, gas_limit, details:details.clone(), depends_on, nonce, condition};
+            let id = wallet.submit_transaction(&msg::source(), txn, msg::value());
+            reply_logged(wallet, Event::Submission{transaction_id:id, details});
+        }
+        Action::ConfirmOnWallet{wallet: child, transaction_id} => {
+            let id = wallet.submit_wallet_vote(&msg::source(), &child, &transaction_id, true, msg::value());
+            let details = wallet.get_transaction(&id).details;
+            reply_logged(wallet, Event::Submission{transaction_id:id, details});
+        }
+        Action::RevokeOnWallet{wallet: child, transaction_id} => {
+            let id = wallet.submit_wallet_vote(&msg::source(), &child, &transaction_id, false, msg::value());
+            let details = wallet.get_transaction(&id).details;
+            reply_logged(wallet, Event::Submission{transaction_id:id, details});
+        }
+        Action::RemoveChildWallet{wallet: child} => {
+            wallet.remove_child_wallet(&child);
+
+            reply_logged(wallet, Event::ChildWalletRemoval{wallet:child});
+        }
+        Action::AmendDescription{transaction_id, description} => {
+            wallet.amend_description(&msg::source(), &transaction_id, description);
+
+            reply_logged(wallet, Event::DescriptionAmendment{transaction_id});
+        }
+        Action::ChangeGasLimit{transaction_id, gas_limit} => {
+            wallet.change_gas_limit(&transaction_id, gas_limit);
+
+            reply_logged(wallet, Event::GasLimitChange{transaction_id, gas_limit});
+        }
+        Action::ConfirmTransaction{transaction_id, comment} => {
+            let result = wallet.confirm_transaction(&msg::source(), &transaction_id, comment);
+            reply_logged(wallet, Event::Confirmation{sender:msg::source().clone(), transaction_id: transaction_id.clone(), dispatched: result == ExecutionResult::Dispatched});
+        }
+        Action::RevokeConfirmation{transaction_id, comment} => {
+            wallet.revoke_confirmation(&msg::source(), &transaction_id, comment);
+            reply_logged(wallet, Event::Revocation{sender:msg::source(), transaction_id:transaction_id});
+        }
+        Action::RejectTransaction{transaction_id, comment} => {
+            let cancelled = wallet.reject_transaction(&msg::source(), &transaction_id, comment);
+            reply_logged(wallet, Event::Rejection{sender:msg::source(), transaction_id, cancelled});
+        }
+        Action::CancelTransaction{transaction_id} => {
+            // the wallet already logged the cancellation
+            wallet.cancel_transaction(&msg::source(), &transaction_id);
+            msg::reply(Event::Cancellation{transaction_id}, 0);
+        }
+        Action::ExpireTransaction{transaction_id} => {
+            wallet.expire_transaction(&transaction_id);
+            msg::reply(Event::Cancellation{transaction_id}, 0);
+        }
+        Action::ClaimBond{transaction_id} => {
+            // the refund is logged by the wallet itself
+            let amount = wallet.claim_bond(&msg::source(), &transaction_id);
+            msg::reply(Event::BondRefund{transaction_id, submitter:msg::source(), amount}, 0);
+        }
+        Action::ChangeBondConfig{bond, ttl} => {
+            wallet.change_bond_config(bond, ttl);
+
+            reply_logged(wallet, Event::BondConfigChange{bond, ttl});
+        }
+        Action::SetAllowance{owner, amount, period, destination} => {
+            wallet.set_allowance(&owner, amount, period, destination);
+            reply_logged(wallet, Event::AllowanceChange{owner, amount, period, destination});
+        }
+        Action::RemoveAllowance{owner} => {
+            wallet.remove_allowance(&owner);
+            reply_logged(wallet, Event::AllowanceRemoval{owner});
+        }
+        Action::SpendAllowance{to, value} => {
+            match wallet.spend_allowance(&msg::source(), &to, value) {
+                Some(remaining) => reply_logged(wallet, Event::AllowanceSpent{owner:msg::source(), to, value, remaining}),
+                // the outcome is logged and sent to the owner once the guard replies
+                None => {msg::reply(Event::AllowanceSpendDispatched{owner:msg::source(), to, value}, 0);},
             }
         }
+        Action::DelegateTo{delegate, until_block} => {
+            wallet.delegate_to(&msg::source(), &delegate, until_block);
+            reply_logged(wallet, Event::Delegation{owner:msg::source(), delegate, until_block});
+        }
+        Action::Undelegate => {
+            wallet.undelegate(&msg::source());
+            reply_logged(wallet, Event::Undelegation{owner:msg::source()});
+        }
+        Action::ExecuteTransaction{transaction_id} => {
+            let result = wallet.execute_transaction(&msg::source(), &transaction_id);
+            reply_execution(wallet, transaction_id, result);
+        }
+        Action::ExecuteScheduled{transaction_id} => {
+            let result = wallet.execute_scheduled(&transaction_id);
+            reply_execution(wallet, transaction_id, result);
+        }
+        Action::RetryTransaction{transaction_id} => {
+            let result = wallet.retry_transaction(&msg::source(), &transaction_id);
+            reply_execution(wallet, transaction_id, result);
+        }
+        Action::AbortExecution{transaction_id} => {
+            wallet.abort_execution(&msg::source(), &transaction_id);
+
+            reply_logged(wallet, Event::ExecutionAborted{transaction_id});
+        }
+        Action::ChangeMaxRetries{max_retries} => {
+            wallet.change_max_retries(max_retries);
+
+            reply_logged(wallet, Event::MaxRetriesChange{max_retries});
+        }
+        Action::PruneTransactions{before_id} => {
+            let count = wallet.prune_transactions(&before_id);
+            let (_, archive_head) = wallet.get_archive(0, 0);
+
+            reply_logged(wallet, Event::TransactionsPruned{before_id, count, archive_head});
+        }
+        Action::SetGuard{guard} => {
+            wallet.set_guard(guard);
+
+            reply_logged(wallet, Event::GuardChange{guard});
+        }
+        Action::Pause => {
+            let paused = wallet.pause(&msg::source());
+
+            reply_logged(wallet, Event::PauseVote{owner:msg::source(), paused});
+        }
+        Action::Unpause => {
+            wallet.unpause();
+
+            reply_logged(wallet, Event::Unpause);
+        }
+        Action::ChangePauseConfig{threshold, max_duration} => {
+            wallet.change_pause_config(threshold, max_duration);
+
+            reply_logged(wallet, Event::PauseConfigChange{threshold, max_duration});
+        }
+        Action::AddListener{listener, filter} => {
+            wallet.add_listener(&listener, filter.clone());
+
+            reply_logged(wallet, Event::ListenerAdded{listener, filter});
+        }
+        Action::RemoveListener{listener} => {
+            wallet.remove_listener(&listener);
+
+            reply_logged(wallet, Event::ListenerRemoved{listener});
+        }
+        Action::EnableModule{module} => {
+            wallet.enable_module(&module);
+
+            reply_logged(wallet, Event::ModuleEnabled{module});
+        }
+        Action::DisableModule{module} => {
+            wallet.disable_module(&module);
+
+            reply_logged(wallet, Event::ModuleDisabled{module});
+        }
+        Action::ExecFromModule{destination, value, data} => {
+            // the outcome is logged and sent to the module once the destination replies
+            let call_id = wallet.exec_from_module(&msg::source(), &destination, &value, &data);
+            msg::reply(Event::ModuleCallDispatched{call_id}, 0);
+        }
+        Action::ReserveGas{amount, duration} => {
+            wallet.reserve_gas(&msg::source(), amount, duration);
+
+            reply_logged(wallet, Event::GasReservation{sender: msg::source(), amount, duration});
+        }
+        Action::ReleaseGas => {
+            let amount = wallet.release_gas();
+
+            reply_logged(wallet, Event::GasRelease{amount});
+        }
         Action::IsConfirmed{transaction_id} => {
             let c = wallet.is_confirmed(&transaction_id);
             msg::reply(Event::IsConfirmed(c), 0);
@@ -132,15 +517,97 @@ async unsafe fn main() {
             let owners = wallet.get_owners();
             msg::reply(Event::GetOwners(owners), 0);
         }
+        Action::GetQuorum => {
+            let mode = wallet.get_quorum_mode();
+            let required = wallet.get_required();
+            msg::reply(Event::GetQuorum{mode, required}, 0);
+        }
+        Action::GetExecutions => {
+            let executions = wallet.get_executions();
+            msg::reply(Event::GetExecutions(executions), 0);
+        }
+        Action::GetNonce => {
+            let (next_nonce, strict) = wallet.get_nonce();
+            msg::reply(Event::GetNonce{next_nonce, strict}, 0);
+        }
+        Action::GetBondConfig => {
+            let (bond, ttl) = wallet.get_bond_config();
+            msg::reply(Event::GetBondConfig{bond, ttl}, 0);
+        }
+        Action::GetOwnerSetVersion => {
+            let (version, snapshot_mode) = wallet.get_owner_set_version();
+            msg::reply(Event::GetOwnerSetVersion{version, snapshot_mode}, 0);
+        }
+        Action::GetGuard => {
+            let guard = wallet.get_guard();
+            msg::reply(Event::GetGuard(guard), 0);
+        }
+        Action::GetModules{from, limit} => {
+            let (modules, next) = wallet.get_modules(from, limit);
+            msg::reply(Event::GetModules{modules, next}, 0);
+        }
+        Action::GetListeners => {
+            let listeners = wallet.get_listeners();
+            msg::reply(Event::GetListeners(listeners), 0);
+        }
+        Action::GetPauseState => {
+            let (paused_until, votes, threshold, max_duration) = wallet.get_pause_state();
+            msg::reply(Event::GetPauseState{paused_until, votes, threshold, max_duration}, 0);
+        }
+        Action::GetInvites => {
+            let invites = wallet.get_invites();
+            msg::reply(Event::GetInvites(invites), 0);
+        }
         Action::GetConfirmations{transaction_id} => {
             let confirmations = wallet.get_confirmations(&transaction_id);
 
             msg::reply(Event::GetConfirmations(confirmations), 0);
         }
-        Action::GetTransactionIds{from, to, pending, executed} => {
-            let ids = wallet.get_transaction_ids(&from, &to, pending, executed);
+        Action::GetConfirmationDetails{transaction_id} => {
+            let details = wallet.get_confirmation_details(&transaction_id);
+
+            msg::reply(Event::GetConfirmationDetails(details), 0);
+        }
+        Action::GetVotes{transaction_id} => {
+            let (approve, reject, abstain) = wallet.get_votes(&transaction_id);
+
+            msg::reply(Event::GetVotes{approve, reject, abstain}, 0);
+        }
+        Action::GetDelegations => {
+            let delegations = wallet.get_delegations();
+
+            msg::reply(Event::GetDelegations(delegations), 0);
+        }
+        Action::GetAllowance{owner} => {
+            let allowance = wallet.get_allowance(&owner);
+
+            msg::reply(Event::GetAllowance(allowance), 0);
+        }
+        Action::GetTransactionIds{from_id, limit, reverse, pending, executed} => {
+            let (ids, next_cursor) = wallet.get_transaction_ids(from_id, limit, reverse, pending, executed);
+
+            msg::reply(Event::GetTransactionIds{ids, next_cursor}, 0);
+        }
+        Action::GetTransaction{transaction_id} => {
+            let transaction = wallet.get_transaction(&transaction_id);
+
+            msg::reply(Event::GetTransaction(transaction), 0);
+        }
+        Action::GetGasReservations => {
+            let reservations = wallet.get_gas_reservations();
+            let total = reservations.iter().map(|r|r.amount).sum();
+
+            msg::reply(Event::GetGasReservations{total, reservations}, 0);
+        }
+        Action::GetEvents{from_seq, limit} => {
+            let events = wallet.get_events(from_seq, limit);
+
+            msg::reply(Event::GetEvents(events), 0);
+        }
+        Action::GetArchive{from_index, limit} => {
+            let (entries, head) = wallet.get_archive(from_index, limit);
 
-            msg::reply(Event::GetTransactionIds(ids), 0);
+            msg::reply(Event::GetArchive{entries, head}, 0);
         }
     }
 }
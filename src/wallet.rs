@@ -1,30 +1,369 @@
 
-use gstd::{exec, msg, prelude::*, ActorId};
+use gstd::{exec, msg, prelude::*, ActorId, MessageId, ReservationId};
+use blake2::{digest::consts::U32, Blake2b, Digest};
+use crate::{Action, Event, GuardAction, GuardReply, Notification, NotificationFilter};
 
 #[allow(non_camel_case_types)]
 pub type uint = u128;
 
 const ZERO_ID: ActorId = ActorId::new([0u8; 32]);
 
+type Blake2b256 = Blake2b<U32>;
+
     /*
      *  Constants
      */
 pub static MAX_OWNER_COUNT:uint = 50;
+pub static DEFAULT_MAX_RETRIES:u32 = 3;
+pub static DEFAULT_PAUSE_THRESHOLD:uint = 1;
+pub static DEFAULT_MAX_PAUSE_DURATION:u32 = 14_400;
+pub static INVITE_DURATION:u32 = 14_400;
+pub static MAX_PAGE_SIZE:u32 = 100;
+pub static MAX_COMMENT_LENGTH:usize = 256;
+pub static MAX_TITLE_LENGTH:usize = 128;
+pub static MAX_DESCRIPTION_LENGTH:usize = 2048;
+pub static MAX_URI_LENGTH:usize = 256;
+pub static MAX_LOG_SIZE:usize = 1024;
+pub static MAX_LISTENER_COUNT:usize = 16;
+pub static EXECUTION_TIMEOUT:u32 = 14_400;
+pub static SCHEDULED_EXECUTION_GAS:u64 = 1_000_000_000;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo, Default)]
+pub enum TransactionStatus {
+    #[default]
+    Pending,
+    Executing,
+    Executed,
+    Cancelled,
+    Scheduled,
+}
+
+/// Human readable context of a transaction, the document itself lives off-chain.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct ProposalDetails {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub content_hash: Option<[u8; 32]>,
+    pub uri: Option<String>,
+}
+
+impl ProposalDetails {
+    fn validate(&self) {
+        assert!(self.title.as_ref().map_or(true, |t|t.len() <= MAX_TITLE_LENGTH), "title is longer than {} bytes", MAX_TITLE_LENGTH);
+        Self::validate_description(&self.description);
+        assert!(self.uri.as_ref().map_or(true, |u|u.len() <= MAX_URI_LENGTH), "uri is longer than {} bytes", MAX_URI_LENGTH);
+    }
+
+    fn validate_description(description:&Option<String>) {
+        assert!(description.as_ref().map_or(true, |d|d.len() <= MAX_DESCRIPTION_LENGTH), "description is longer than {} bytes", MAX_DESCRIPTION_LENGTH);
+    }
+}
+
+/// Proposal of an owner, everything a submitted transaction is made of but its bond.
+#[derive(Debug, Clone)]
+pub struct NewTransaction {
+    pub destination: ActorId,
+    pub value: uint,
+    pub data: Vec<u8>,
+    /// Gas limit for the call, `None` means no limit.
+    pub gas_limit: Option<u64>,
+    pub details: ProposalDetails,
+    /// Transactions which have to be executed before this one.
+    pub depends_on: Vec<uint>,
+    /// Nonce in strict order mode, reusing a pending nonce submits a replacement, `None` takes the next free one.
+    pub nonce: Option<u64>,
+    /// Condition which has to hold when the transaction is executed.
+    pub condition: Option<Condition>,
+}
+
 #[derive(Default)]
 struct Transaction {
+    submitter:ActorId,
+    details:ProposalDetails,
     destination:ActorId ,
     value:uint ,
     data:Vec<u8>,
-    executed:bool,
+    status:TransactionStatus,
+    gas_limit:Option<u64>,
+    failure_count:u32,
+    last_failure:Option<String>,
+    snapshot:Option<OwnerSnapshot>,
+    bond:uint,
+    expires_at:Option<u32>,
+    depends_on:Vec<uint>,
+    nonce:Option<u64>,
+    condition:Option<Condition>,
+    wallet_vote:bool,
+}
+
+/// Compact summary of a pruned transaction, `chain_hash` covers the summary and all earlier ones.
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub struct ArchivedTransaction {
+    pub id: uint,
+    pub destination: ActorId,
+    pub value: uint,
+    pub status: TransactionStatus,
+    pub payload_hash: [u8; 32],
+    pub chain_hash: [u8; 32],
+}
+
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub struct TransactionInfo {
+    pub submitter: ActorId,
+    pub details: ProposalDetails,
+    pub destination: ActorId,
+    pub value: uint,
+    pub data: Vec<u8>,
+    pub status: TransactionStatus,
+    /// Gas limit for the call, `None` means no limit. The gas the destination actually uses is charged
+    /// to its own message and isn't reported back in the reply, so the wallet can't record it.
+    pub gas_limit: Option<u64>,
+    pub failure_count: u32,
+    pub last_failure: Option<String>,
+    pub snapshot: Option<OwnerSnapshot>,
+    pub bond: uint,
+    pub expires_at: Option<u32>,
+    pub depends_on: Vec<uint>,
+    pub nonce: Option<u64>,
+    pub condition: Option<Condition>,
+    /// Proposed with `ConfirmOnWallet` or `RevokeOnWallet`, a vote in a child wallet.
+    pub wallet_vote: bool,
+}
+
+/// Owner set version and requirement a transaction is voted with, recorded at submission.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct OwnerSnapshot {
+    pub version: u32,
+    pub required: uint,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum ConfirmationAction {
+    Confirm,
+    Revoke,
+    Reject,
+}
+
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub struct ConfirmationRecord {
+    pub owner: ActorId,
+    /// Delegate which acted for the owner, if any.
+    pub delegate: Option<ActorId>,
+    pub action: ConfirmationAction,
+    pub block: u32,
+    pub timestamp: u64,
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub struct LogEntry {
+    pub seq: u64,
+    pub block: u32,
+    pub actor: ActorId,
+    pub event: Event,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct Delegation {
+    pub owner: ActorId,
+    pub delegate: ActorId,
+    pub until_block: u32,
+}
+
+/// Pending ownership, the invitee becomes an owner once it accepts.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct OwnerInvite {
+    pub invitee: ActorId,
+    pub replaces: Option<ActorId>,
+    pub expires_at: u32,
+}
+
+/// How the reply of a condition query is compared with the expected bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum Comparator {
+    /// The reply bytes equal the expected bytes.
+    Equals,
+    /// Both decode as `uint` and the reply is greater.
+    GreaterThan,
+    /// Both decode as `uint` and the reply is less.
+    LessThan,
+}
+
+/// External condition checked right before a transaction is executed.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum Condition {
+    /// Sends `payload` to `program` and compares its reply with `expected`.
+    Query{program:ActorId, payload:Vec<u8>, comparator:Comparator, expected:Vec<u8>},
+    /// The block height is within `from..=to`.
+    BlockRange{from:u32, to:u32},
+}
+
+impl Condition {
+    fn validate(&self) {
+        match self {
+            Condition::Query{program, comparator, expected, ..} => {
+                assert!(*program != ZERO_ID, "condition program is the zero address");
+                if *comparator != Comparator::Equals {
+                    assert!(uint::decode(&mut expected.as_ref()).is_ok(), "expected value is not a number");
+                }
+            },
+            Condition::BlockRange{from, to} => assert!(from <= to, "empty block range"),
+        }
+    }
+
+    // Compares the reply of a condition query with the expected bytes.
+    fn compare(comparator:Comparator, expected:&[u8], reply:&[u8]) -> Result<(), String> {
+        let holds = match comparator {
+            Comparator::Equals => reply == expected,
+            Comparator::GreaterThan | Comparator::LessThan => {
+                let value = uint::decode(&mut reply.as_ref()).map_err(|_|String::from("condition: reply is not a number"))?;
+                let expected = uint::decode(&mut expected.as_ref()).unwrap();
+                if comparator == Comparator::GreaterThan {value > expected} else {value < expected}
+            },
+        };
+        if holds {Ok(())} else {Err(String::from("condition: query reply doesn't match"))}
+    }
+}
+
+/// Step of an execution waiting for a reply in `handle_reply`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Encode, Decode, TypeInfo)]
+pub enum ExecutionStage {
+    /// The condition query is sent.
+    Condition,
+    /// The guard is asked to approve the call.
+    Guard,
+    /// The call is sent to the destination.
+    Call,
+    /// The guard is told the outcome, which is final already.
+    AfterCheck,
+    /// The guard is asked to approve the call of a module, the ID is the one of the module call.
+    ModuleGuard,
+    /// The call of a module is sent, the ID is the one of the module call.
+    Module,
+    /// The execution is sent to the wallet itself, paid from the gas reservation pool.
+    Scheduled,
+    /// The guard is asked to approve an allowance spend, the ID is the one of the spend.
+    AllowanceGuard,
+}
+
+/// Outcome of an execution as far as it got before the message handling ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionResult {
+    /// Nothing has been executed, the transaction isn't ready or the message is stale.
+    NotExecuted,
+    /// The call succeeded.
+    Executed,
+    /// The call failed, the transaction can be retried.
+    Failed,
+    /// The execution is scheduled from the gas reservation pool.
+    Scheduled,
+    /// The guard rejected the call.
+    GuardRejected,
+    /// The condition of the transaction doesn't hold.
+    ConditionNotMet,
+    /// A message is in flight, the outcome is settled by its reply.
+    Dispatched,
+}
+
+/// How the number of required confirmations follows the owner set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo, Default)]
+pub enum QuorumMode {
+    /// `required` is set explicitly and only lowered when owners are removed.
+    #[default]
+    Absolute,
+    /// `required` is numerator/denominator of the owners rounded up, at least `floor`.
+    Fraction{numerator:u32, denominator:u32, floor:uint},
+}
+
+struct Allowance {
+    amount:uint,
+    period:u32,
+    destination:Option<ActorId>,
+    period_start:u32,
+    spent:uint,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct AllowanceInfo {
+    pub amount: uint,
+    pub period: u32,
+    pub destination: Option<ActorId>,
+    pub period_start: u32,
+    pub remaining: uint,
+}
+
+struct AllowanceSpend {
+    owner:ActorId,
+    to:ActorId,
+    value:uint,
+    period_start:u32,
+}
+
+struct ModuleCall {
+    module:ActorId,
+    destination:ActorId,
+    value:uint,
+    data:Vec<u8>,
 }
+
+struct GasReservation {
+    id:ReservationId,
+    amount:u64,
+    expires_at:u32,
+}
+
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub struct GasReservationInfo {
+    pub amount: u64,
+    pub expires_at: u32,
+}
+
 #[derive(Default)]
 pub struct MultiSigWallet {
     transactions:  BTreeMap<uint, Transaction>,
+    pending_ids: BTreeSet<uint>,
+    executed_ids: BTreeSet<uint>,
+    cancelled_ids: BTreeSet<uint>,
+    dependents: BTreeMap<uint, BTreeSet<uint>>,
+    nonce_mode: bool,
+    next_nonce: u64,
+    nonces: BTreeMap<u64, BTreeSet<uint>>,
+    in_flight: BTreeMap<MessageId, (uint, ExecutionStage, u32)>,
+    module_calls: BTreeMap<uint, ModuleCall>,
+    module_call_count: uint,
+    executed_count: uint,
+    archive: Vec<ArchivedTransaction>,
+    archive_head: [u8; 32],
+    pruned: BTreeMap<uint, TransactionStatus>,
     confirmations: BTreeMap<uint, BTreeMap<ActorId, bool>>,
+    confirmation_history: BTreeMap<uint, Vec<ConfirmationRecord>>,
+    rejections: BTreeMap<uint, BTreeSet<ActorId>>,
     is_owner :BTreeMap<ActorId, bool> ,
     owners: Vec<ActorId>,
+    owner_set_version: u32,
+    owner_sets: BTreeMap<u32, Vec<ActorId>>,
+    snapshot_mode: bool,
+    invites: BTreeMap<ActorId, OwnerInvite>,
+    delegations: BTreeMap<ActorId, Delegation>,
+    child_wallets: BTreeSet<ActorId>,
+    allowances: BTreeMap<ActorId, Allowance>,
+    allowance_spends: BTreeMap<uint, AllowanceSpend>,
+    allowance_spend_count: uint,
     required: uint,
+    quorum_mode: QuorumMode,
     transaction_count: uint,
+    bond: uint,
+    proposal_ttl: u32,
+    reservations: Vec<GasReservation>,
+    max_retries: u32,
+    guard: Option<ActorId>,
+    pause_threshold: uint,
+    max_pause_duration: u32,
+    pause_votes: BTreeSet<ActorId>,
+    paused_until: Option<u32>,
+    modules: BTreeSet<ActorId>,
+    listeners: BTreeMap<ActorId, NotificationFilter>,
+    event_log: VecDeque<LogEntry>,
+    next_seq: u64,
 }
 
 impl MultiSigWallet {
@@ -51,6 +390,14 @@ impl MultiSigWallet {
         assert!(self.is_owner.get(owner).unwrap_or(&false));
     }
 
+    #[inline]
+    fn voter_exists(&self, transaction_id:&uint, owner:&ActorId) {
+        match self.transactions.get(transaction_id).and_then(|txn|txn.snapshot) {
+            Some(snapshot) => assert!(self.owner_sets[&snapshot.version].contains(owner), "not an owner of the transaction snapshot"),
+            None => self.owner_exists(owner),
+        }
+    }
+
     #[inline]
     fn confirmed(&self, transaction_id:&uint, owner:&ActorId) {
         // assert!(self.confirmations[transaction_id][owner]);
@@ -75,10 +422,47 @@ impl MultiSigWallet {
     fn not_executed(&self, transaction_id:&uint) {
         // assert!(!self.transactions[transaction_id].executed);
         if let Some(transaction) = self.transactions.get(transaction_id) {
-            assert!(!transaction.executed);
+            assert!(transaction.status != TransactionStatus::Executed);
+        }
+    }
+    #[inline]
+    fn not_executing(&self, transaction_id:&uint) {
+        if let Some(transaction) = self.transactions.get(transaction_id) {
+            assert!(!matches!(transaction.status, TransactionStatus::Executing | TransactionStatus::Scheduled), "transaction {} is being executed", transaction_id);
+        }
+    }
+    #[inline]
+    fn retries_left(&self, transaction_id:&uint) {
+        if let Some(transaction) = self.transactions.get(transaction_id) {
+            assert!(transaction.failure_count <= self.max_retries, "transaction {} failed too many times", transaction_id);
+        }
+    }
+    #[inline]
+    fn not_cancelled(&self, transaction_id:&uint) {
+        if let Some(transaction) = self.transactions.get(transaction_id) {
+            assert!(transaction.status != TransactionStatus::Cancelled, "transaction {} is cancelled", transaction_id);
+        }
+    }
+    #[inline]
+    fn not_expired(&self, transaction_id:&uint) {
+        if let Some(expires_at) = self.transactions.get(transaction_id).and_then(|txn|txn.expires_at) {
+            assert!(exec::block_height() < expires_at, "transaction {} has expired", transaction_id);
         }
     }
     #[inline]
+    fn ready(&self, transaction_id:&uint) {
+        assert!(self.is_ready(transaction_id), "transaction {} waits for its dependencies or an earlier nonce", transaction_id);
+    }
+    #[inline]
+    fn not_child_wallet(&self, owner:&ActorId) {
+        assert!(!self.child_wallets.contains(owner), "ownership cycle, {:?} is a wallet this wallet votes in", owner);
+    }
+    #[inline]
+    fn when_not_paused(&self, destination:&ActorId) {
+        // governance calls of the wallet to itself, among them Unpause, go through a pause
+        assert!(!self.is_paused() || *destination == exec::program_id(), "wallet is paused");
+    }
+    #[inline]
     fn not_null(_address:&ActorId) {
         assert!(_address != &ZERO_ID);
     }
@@ -108,11 +492,50 @@ impl MultiSigWallet {
         Self::valid_requirement(len, _required);
         let mut ret = Self {
             transactions: BTreeMap::new(),
+            pending_ids: BTreeSet::new(),
+            executed_ids: BTreeSet::new(),
+            cancelled_ids: BTreeSet::new(),
+            dependents: BTreeMap::new(),
+            nonce_mode: false,
+            next_nonce: 0,
+            nonces: BTreeMap::new(),
+            in_flight: BTreeMap::new(),
+            module_calls: BTreeMap::new(),
+            module_call_count: 0,
+            executed_count: 0,
+            archive: vec![],
+            archive_head: [0u8; 32],
+            pruned: BTreeMap::new(),
             confirmations: BTreeMap::new(),
+            confirmation_history: BTreeMap::new(),
+            rejections: BTreeMap::new(),
             is_owner: BTreeMap::new(),
             owners: vec![],
+            owner_set_version: 0,
+            owner_sets: BTreeMap::new(),
+            snapshot_mode: false,
+            invites: BTreeMap::new(),
+            delegations: BTreeMap::new(),
+            child_wallets: BTreeSet::new(),
+            allowances: BTreeMap::new(),
+            allowance_spends: BTreeMap::new(),
+            allowance_spend_count: 0,
             required: _required ,
+            quorum_mode: QuorumMode::Absolute,
             transaction_count: 0,
+            bond: 0,
+            proposal_ttl: 0,
+            reservations: vec![],
+            max_retries: DEFAULT_MAX_RETRIES,
+            guard: None,
+            pause_threshold: DEFAULT_PAUSE_THRESHOLD,
+            max_pause_duration: DEFAULT_MAX_PAUSE_DURATION,
+            pause_votes: BTreeSet::new(),
+            paused_until: None,
+            modules: BTreeSet::new(),
+            listeners: BTreeMap::new(),
+            event_log: VecDeque::new(),
+            next_seq: 0,
         };
         for owner in _owners {
             assert!(ZERO_ID != *owner && !ret.is_owner.get(owner).unwrap_or(&false));
@@ -123,17 +546,66 @@ impl MultiSigWallet {
         ret
     }
 
-    /// @dev Allows to add a new owner. Transaction has to be sent by wallet.
+    /// @dev Allows to invite a new owner, it is added once it accepts. Transaction has to be sent by wallet.
     /// @param owner Address of new owner.
-    pub fn add_owner(&mut self, owner:&ActorId)
+    /// @return Returns the invite.
+    pub fn add_owner(&mut self, owner:&ActorId)->OwnerInvite
     {
         self.only_wallet();
         self.owner_does_not_exist(owner);
         Self::not_null(owner);
+        self.not_child_wallet(owner);
         Self::valid_requirement((self.owners.len() + 1) as uint, self.required);
-        self.is_owner.insert(owner.clone(), true);
-        self.owners.push(owner.clone());
-        // OwnerAddition(owner);
+        // OwnerInvitation(owner);
+        self.invite(owner, None)
+    }
+
+    fn invite(&mut self, invitee:&ActorId, replaces:Option<ActorId>)->OwnerInvite
+    {
+        let now = exec::block_height();
+        self.invites.retain(|_, invite|invite.expires_at > now);
+        let invite = OwnerInvite{
+            invitee: *invitee,
+            replaces,
+            expires_at: now.saturating_add(INVITE_DURATION),
+        };
+        self.invites.insert(*invitee, invite.clone());
+        invite
+    }
+
+    /// @dev Allows an invited account to accept the ownership, the owner set changes only now.
+    /// @return Returns the accepted invite.
+    pub fn accept_ownership(&mut self, sender:&ActorId)->OwnerInvite
+    {
+        let invite = self.invites.remove(sender).expect("no invite");
+        assert!(invite.expires_at > exec::block_height(), "invite expired");
+        // the owner set may have changed since the invite was approved
+        self.owner_does_not_exist(sender);
+        self.not_child_wallet(sender);
+        match invite.replaces {
+            Some(owner) => {
+                self.owner_exists(&owner);
+                let idx = self.owners.iter().position(|x|*x == owner).unwrap();
+                self.owners[idx] = *sender;
+                self.is_owner.insert(owner, false);
+                self.is_owner.insert(*sender, true);
+                self.clear_delegations(&owner);
+                self.clear_delegations(sender);
+                self.allowances.remove(&owner);
+                self.owner_set_version += 1;
+                // OwnerReplace(owner, msg.sender);
+            },
+            None => {
+                Self::valid_requirement((self.owners.len() + 1) as uint, self.required);
+                self.is_owner.insert(*sender, true);
+                self.owners.push(*sender);
+                self.clear_delegations(sender);
+                self.owner_set_version += 1;
+                self.adjust_requirement();
+                // OwnerAddition(msg.sender);
+            },
+        }
+        invite
     }
 
 
@@ -146,23 +618,170 @@ impl MultiSigWallet {
         self.is_owner.entry(owner.clone()).and_modify(|e|*e = false).or_insert(false);
         let idx = self.owners.iter().position(|x|x == owner).unwrap();
         self.owners.swap_remove(idx);
-        if self.required > self.owners.len().try_into().unwrap() {
-            self.change_requirement(self.owners.len().try_into().unwrap());
-        }
+        self.clear_delegations(owner);
+        self.allowances.remove(owner);
+        self.pause_threshold = self.pause_threshold.min(self.owners.len() as uint);
+        self.owner_set_version += 1;
+        self.adjust_requirement();
     }
 
-    /// @dev Allows to replace an owner with a new owner. Transaction has to be sent by wallet.
+    /// @dev Allows to invite a new owner replacing an owner once it accepts. Transaction has to be sent by wallet.
     /// @param owner Address of owner to be replaced.
     /// @param newOwner Address of new owner.
-    pub fn replace_owner(&mut self, owner:&ActorId, new_owner:&ActorId)        
+    /// @return Returns the invite.
+    pub fn replace_owner(&mut self, owner:&ActorId, new_owner:&ActorId)->OwnerInvite
     {
         self.only_wallet();
         self.owner_exists(owner);
         self.owner_does_not_exist(new_owner);
-        let idx = self.owners.iter().position(|x|x == owner).unwrap();
-        self.owners[idx] = new_owner.clone();
-        self.is_owner.insert(owner.clone(), false);
-        self.is_owner.insert(new_owner.clone(), true);
+        Self::not_null(new_owner);
+        self.not_child_wallet(new_owner);
+        // OwnerInvitation(newOwner);
+        self.invite(new_owner, Some(*owner))
+    }
+
+    /// @dev Allows to let an owner transfer value alone up to an amount per period. Transaction has to be sent by wallet.
+    /// @param owner Address of owner.
+    /// @param amount Value the owner can spend per period.
+    /// @param period Length of a period in blocks.
+    /// @param destination Only allowed recipient, `None` allows any.
+    pub fn set_allowance(&mut self, owner:&ActorId, amount:uint, period:u32, destination:Option<ActorId>)
+    {
+        self.only_wallet();
+        self.owner_exists(owner);
+        assert!(period > 0, "period must be at least one block");
+        self.allowances.insert(*owner, Allowance{
+            amount,
+            period,
+            destination,
+            period_start: exec::block_height(),
+            spent: 0,
+        });
+        // AllowanceChange(owner, amount, period, destination);
+    }
+
+    /// @dev Allows to remove the allowance of an owner. Transaction has to be sent by wallet.
+    /// @param owner Address of owner.
+    pub fn remove_allowance(&mut self, owner:&ActorId)
+    {
+        self.only_wallet();
+        assert!(self.allowances.remove(owner).is_some(), "no allowance");
+        // AllowanceRemoval(owner);
+    }
+
+    /// @dev Allows an owner to transfer value within its allowance without confirmations.
+    /// With a guard the transfer waits for its approval, the outcome is then logged and sent to the owner.
+    /// @param to Recipient of the value.
+    /// @param value Value to transfer.
+    /// @return Returns the allowance left in the current period, `None` while the guard decides.
+    pub fn spend_allowance(&mut self, sender:&ActorId, to:&ActorId, value:uint)->Option<uint>
+    {
+        self.owner_exists(sender);
+        Self::not_null(to);
+        assert!(!self.is_paused(), "wallet is paused");
+        let now = exec::block_height();
+        let allowance = self.allowances.get_mut(sender).expect("no allowance");
+        if let Some(destination) = allowance.destination {
+            assert!(destination == *to, "allowance is restricted to {:?}", destination);
+        }
+        let elapsed = now.saturating_sub(allowance.period_start);
+        if elapsed >= allowance.period {
+            allowance.period_start = now - elapsed % allowance.period;
+            allowance.spent = 0;
+        }
+        let spent = allowance.spent.checked_add(value).expect("overflow");
+        assert!(spent <= allowance.amount, "allowance exceeded");
+        allowance.spent = spent;
+        let remaining = allowance.amount - spent;
+        let period_start = allowance.period_start;
+        if let Some(guard) = self.guard_for(to) {
+            // the value is held back from the allowance until the guard decides
+            let spend_id = self.allowance_spend_count;
+            self.allowance_spend_count += 1;
+            self.allowance_spends.insert(spend_id, AllowanceSpend{owner:*sender, to:*to, value, period_start});
+            let check = GuardAction::CheckAllowanceSpend{owner:*sender, to:*to, value};
+            let sent = msg::send(guard, check, 0).map_err(|e|format!("guard: {:?}", e));
+            self.dispatch(&spend_id, ExecutionStage::AllowanceGuard, sent);
+            return None;
+        }
+        msg::send_bytes(*to, b"", value).expect("unable to transfer value");
+        // AllowanceSpent(msg.sender, to, value, remaining);
+        Some(remaining)
+    }
+
+    // Sends the value of a spend the guard approved, a rejected one gives its value back to the allowance.
+    fn settle_allowance_spend(&mut self, spend_id:&uint, verdict:Result<(), String>)->ExecutionResult
+    {
+        let spend = match self.allowance_spends.remove(spend_id) {
+            Some(spend) => spend,
+            None => return ExecutionResult::NotExecuted,
+        };
+        // the owner may have lost its allowance, or the wallet been paused, meanwhile
+        let sent = verdict
+            .and_then(|_|if self.is_paused() {Err(String::from("wallet is paused"))} else {Ok(())})
+            .and_then(|_|if self.allowances.contains_key(&spend.owner) {Ok(())} else {Err(String::from("no allowance"))})
+            .and_then(|_|msg::send_bytes(spend.to, b"", spend.value).map(|_|()).map_err(|e|format!("{:?}", e)));
+        let (event, result) = match sent {
+            Ok(()) => {
+                let remaining = self.get_allowance(&spend.owner).map_or(0, |a|a.remaining);
+                (Event::AllowanceSpent{owner:spend.owner, to:spend.to, value:spend.value, remaining}, ExecutionResult::Executed)
+            },
+            Err(reason) => {
+                // a new period has already dropped the value from the allowance
+                if let Some(allowance) = self.allowances.get_mut(&spend.owner) {
+                    if allowance.period_start == spend.period_start {
+                        allowance.spent = allowance.spent.saturating_sub(spend.value);
+                    }
+                }
+                (Event::AllowanceSpendRejection{owner:spend.owner, to:spend.to, value:spend.value, reason}, ExecutionResult::GuardRejected)
+            },
+        };
+        self.log_event(&spend.owner, event.clone());
+        // the SpendAllowance message of the owner has been answered already
+        let _ = msg::send(spend.owner, event, 0);
+        result
+    }
+
+    /// @dev Allows an owner to let another account confirm and revoke on its behalf until a block.
+    /// @param delegate Account acting for the owner, it can't be an owner itself.
+    /// @param untilBlock Last block the delegation is valid.
+    pub fn delegate_to(&mut self, sender:&ActorId, delegate:&ActorId, until_block:u32)
+    {
+        self.owner_exists(sender);
+        self.owner_does_not_exist(delegate);
+        Self::not_null(delegate);
+        assert!(until_block >= exec::block_height(), "delegation already expired");
+        let now = exec::block_height();
+        self.delegations.retain(|_, d|d.until_block >= now);
+        assert!(!self.delegations.values().any(|d|d.delegate == *delegate && d.owner != *sender), "account is already a delegate of another owner");
+        self.delegations.insert(*sender, Delegation{owner: *sender, delegate: *delegate, until_block});
+        // Delegation(msg.sender, delegate, untilBlock);
+    }
+
+    /// @dev Allows an owner to end its delegation.
+    pub fn undelegate(&mut self, sender:&ActorId)
+    {
+        self.owner_exists(sender);
+        assert!(self.delegations.remove(sender).is_some(), "no delegation");
+        // Undelegation(msg.sender);
+    }
+
+    // Drops delegations given by or to the account.
+    fn clear_delegations(&mut self, account:&ActorId)
+    {
+        self.delegations.retain(|owner, d|owner != account && d.delegate != *account);
+    }
+
+    // Owner the sender acts for, itself unless it is the delegate of an owner.
+    fn acting_owner(&self, sender:&ActorId) -> ActorId
+    {
+        if *self.is_owner.get(sender).unwrap_or(&false) {
+            return *sender;
+        }
+        let now = exec::block_height();
+        self.delegations.values()
+            .find(|d|d.delegate == *sender && d.until_block >= now)
+            .map_or(*sender, |d|d.owner)
     }
 
     /// @dev Allows to change the number of required confirmations. Transaction has to be sent by wallet.
@@ -170,185 +789,1412 @@ impl MultiSigWallet {
     pub fn change_requirement(&mut self, _required:uint)
     {
         self.only_wallet();
+        assert!(self.quorum_mode == QuorumMode::Absolute, "requirement follows the quorum fraction");
         Self::valid_requirement(self.owners.len().try_into().unwrap(), _required);
         self.required = _required;
         // RequirementChange(_required);
     }
 
-    /// @dev Allows an owner to submit and confirm a transaction.
-    /// @param destination Transaction target address.
-    /// @param value Transaction ether value.
-    /// @param data Transaction data payload.
-    /// @return Returns transaction ID.
-    pub async fn submit_transaction(&mut self, sender:&ActorId, destination:&ActorId, value:&uint, data:&[u8])->uint
+    /// @dev Allows to change how the requirement follows the owner set. Transaction has to be sent by wallet.
+    /// @param mode New quorum mode, a fraction mode recomputes the requirement right away.
+    pub fn change_quorum_mode(&mut self, mode:QuorumMode)
     {
-        let transaction_id = self.add_transaction(destination, value, data);
-        self.confirm_transaction(sender, &transaction_id).await;
-        transaction_id
+        self.only_wallet();
+        if let QuorumMode::Fraction{numerator, denominator, floor} = mode {
+            assert!(numerator > 0 && numerator <= denominator, "invalid quorum fraction");
+            assert!(floor > 0, "quorum floor must be at least one");
+        }
+        self.quorum_mode = mode;
+        self.adjust_requirement();
+        // QuorumModeChange(mode);
     }
 
-    /// @dev Allows an owner to confirm a transaction.
-    /// @param transactionId Transaction ID.
-    pub async fn confirm_transaction(&mut self, sender:&ActorId, transaction_id:&uint)->i32
+    /// @dev Allows to change the bond attached to new transactions and how long they stay open. Transaction has to be sent by wallet.
+    /// @param bond Minimum value a submitter attaches, zero disables the bond.
+    /// @param ttl Number of blocks a transaction can be confirmed for, zero means no expiry.
+    pub fn change_bond_config(&mut self, bond:uint, ttl:u32)
     {
-        self.owner_exists(sender);
-        // self.transaction_exists(transaction_id);
-        assert!(self.transactions.get(transaction_id).unwrap().destination != ZERO_ID);
+        self.only_wallet();
+        self.bond = bond;
+        self.proposal_ttl = ttl;
+        // BondConfigChange(bond, ttl);
+    }
 
-        self.not_confirmed(transaction_id, sender);
-        self.confirmations.entry(transaction_id.clone()).or_insert_with(||BTreeMap::new()).entry(sender.clone()).and_modify(|e| *e = true).or_insert(true);
-        // Confirmation(msg.sender, transaction_id);
-        self.execute_transaction(sender, transaction_id).await
+    /// @dev Allows to execute new transactions strictly in the order of their nonces. Transaction has to be sent by wallet.
+    /// Strict order can't be turned off while a transaction with a nonce is pending.
+    /// @param enabled Give new transactions a nonce and only execute the next one if true.
+    pub fn change_nonce_mode(&mut self, enabled:bool)
+    {
+        self.only_wallet();
+        // executed out of order, a nonce would leave its slot open to a replacement
+        let pending = self.nonces.values().flatten().any(|id|self.status_of(id) == Some(TransactionStatus::Pending));
+        assert!(enabled || !pending, "transactions with a nonce are still pending");
+        self.nonce_mode = enabled;
+        // NonceModeChange(enabled);
     }
 
-    /// @dev Allows an owner to revoke a confirmation for a transaction.
-    /// @param transactionId Transaction ID.
-    pub fn revoke_confirmation(&mut self, sender:&ActorId, transaction_id:&uint)
+    /// @dev Allows to make new transactions keep the owner set and requirement of their submission. Transaction has to be sent by wallet.
+    /// @param enabled Snapshot new transactions if true, vote them with the current owner set otherwise.
+    pub fn change_snapshot_mode(&mut self, enabled:bool)
     {
-        self.owner_exists(sender);
-        self.confirmed(transaction_id, sender);
-        self.not_executed(transaction_id);
-        // self.confirmations.entry[transactionId][msg.sender] = false;
-        self.confirmations.entry(*transaction_id).or_insert_with(||BTreeMap::new()).entry(*sender).and_modify(|e|*e = false).or_insert(false);
-        // Revocation(msg.sender, transaction_id);
+        self.only_wallet();
+        self.snapshot_mode = enabled;
+        // SnapshotModeChange(enabled);
     }
 
-    /// @dev Allows anyone to execute a confirmed transaction.
+    // Recomputes the requirement after the owner set or the quorum mode changed.
+    fn adjust_requirement(&mut self)
+    {
+        let owner_count = self.owners.len() as uint;
+        let to = match self.quorum_mode {
+            QuorumMode::Absolute => self.required.min(owner_count),
+            QuorumMode::Fraction{numerator, denominator, floor} => {
+                let (numerator, denominator) = (numerator as uint, denominator as uint);
+                let share = (owner_count * numerator + denominator - 1) / denominator;
+                share.max(floor).min(owner_count)
+            },
+        };
+        Self::valid_requirement(owner_count, to);
+        if to != self.required {
+            let from = self.required;
+            self.required = to;
+            self.log_event(&msg::source(), Event::RequirementChange{from, to});
+        }
+    }
+
+    /// @dev Allows to change the gas limit of a not yet executed transaction. Transaction has to be sent by wallet.
     /// @param transactionId Transaction ID.
-    pub async fn execute_transaction(&mut self, sender:&ActorId, transaction_id:&uint)->i32
+    /// @param gasLimit New gas limit, `None` means no limit.
+    pub fn change_gas_limit(&mut self, transaction_id:&uint, gas_limit:Option<u64>)
     {
-        self.owner_exists(sender);
-        self.confirmed(transaction_id, sender);
+        self.only_wallet();
+        self.not_executing(transaction_id);
         self.not_executed(transaction_id);
-        if self.is_confirmed(transaction_id) {
-            let mut txn = self.transactions.get_mut(transaction_id).unwrap();
-            if Self::external_call(&txn.destination, &txn.value, &txn.data).await {
-                txn.executed = true;
-                // Execution(transactionId);
-                return 1;
-            }
-            else {
-                // ExecutionFailure(transactionId);
-                txn.executed = false;
-                return 2;
-            }
-        }
-        0
+        self.transactions.get_mut(transaction_id).expect("transaction does not exist").gas_limit = gas_limit;
+        // GasLimitChange(transactionId, gasLimit);
     }
 
-    // call has been separated into its own fn in order to take advantage
-    // of the Solidity's code generator to produce a loop that copies tx.data into memory.
-    async fn external_call(destination:&ActorId, value:&uint, data:&[u8]) -> bool {
-        match msg::send_bytes_and_wait_for_reply(destination.to_owned(), data, value.to_owned()).await {
-            Ok(_bytes) => {
-                // msg::reply_bytes(bytes, 0);
-                true
-            },
-            Err(_e)   => false,
+    /// @dev Allows to change how many times a failed transaction may be executed again. Transaction has to be sent by wallet.
+    /// @param maxRetries Number of allowed retries after the first failure.
+    pub fn change_max_retries(&mut self, max_retries:u32)
+    {
+        self.only_wallet();
+        self.max_retries = max_retries;
+        // MaxRetriesChange(maxRetries);
+    }
+
+    /// @dev Allows to prune finished transactions, their summaries are kept in a hash chained archive.
+    /// Transaction has to be sent by wallet.
+    /// @param beforeId Only transactions with a lower ID are pruned.
+    /// @return Returns number of pruned transactions.
+    pub fn prune_transactions(&mut self, before_id:&uint)->u32
+    {
+        self.only_wallet();
+        let mut ids:Vec<uint> = self.executed_ids.range(..*before_id).cloned().collect();
+        ids.extend(self.cancelled_ids.range(..*before_id));
+        // a bond still to be claimed keeps its transaction
+        ids.retain(|id|self.transactions[id].bond == 0);
+        ids.sort_unstable();
+        for id in ids.iter() {
+            let txn = self.transactions.remove(id).unwrap();
+            self.executed_ids.remove(id);
+            self.cancelled_ids.remove(id);
+            self.confirmations.remove(id);
+            self.confirmation_history.remove(id);
+            self.rejections.remove(id);
+            self.dependents.remove(id);
+            self.pruned.insert(*id, txn.status);
+
+            let payload_hash:[u8; 32] = Blake2b256::digest(&txn.data).into();
+            let summary = (id, txn.destination, txn.value, txn.status, payload_hash);
+            let mut hasher = Blake2b256::new();
+            hasher.update(self.archive_head);
+            hasher.update(summary.encode());
+            self.archive_head = hasher.finalize().into();
+            self.archive.push(ArchivedTransaction{
+                id: *id,
+                destination: txn.destination,
+                value: txn.value,
+                status: txn.status,
+                payload_hash,
+                chain_hash: self.archive_head,
+            });
         }
+        // TransactionsPruned(beforeId, count, archiveHead);
+        ids.len() as u32
     }
 
-    /// @dev Returns the confirmation status of a transaction.
-    /// @param transactionId Transaction ID.
-    /// @return Confirmation status.
-    pub fn is_confirmed(&self, transaction_id:&uint)->bool
+    /// @dev Allows to set or remove the guard program checking transactions before and after execution.
+    /// Transaction has to be sent by wallet.
+    /// @param guard Address of the guard program, `None` removes it.
+    pub fn set_guard(&mut self, guard:Option<ActorId>)
     {
-        let mut count = 0;
-        let cfm_dict = self.confirmations.get(transaction_id).unwrap();
-        for confirmed in cfm_dict.values() {
-            if *confirmed {
-                count += 1;
-            }
-            if count == self.required {
-                return true;
-            }
+        self.only_wallet();
+        if let Some(guard) = &guard {
+            Self::not_null(guard);
         }
+        self.guard = guard;
+        // GuardChange(guard);
+    }
 
+    /// @dev Allows an owner to vote for an emergency pause, the wallet is paused once enough owners voted.
+    /// While paused only calls of the wallet to itself can be submitted and executed.
+    /// @return Returns true if this vote paused the wallet.
+    pub fn pause(&mut self, sender:&ActorId)->bool
+    {
+        self.owner_exists(sender);
+        assert!(!self.is_paused(), "wallet is already paused");
+        self.pause_votes.insert(*sender);
+        let votes = self.owners.iter().filter(|owner|self.pause_votes.contains(owner)).count() as uint;
+        // PauseVote(msg.sender);
+        if votes >= self.pause_threshold {
+            self.pause_votes.clear();
+            self.paused_until = Some(exec::block_height().saturating_add(self.max_pause_duration));
+            return true;
+        }
         false
     }
 
-    /*
-     * Internal fns
-     */
-    /// @dev Adds a new transaction to the transaction mapping, if transaction does not exist yet.
-    /// @param destination Transaction target address.
-    /// @param value Transaction ether value.
-    /// @param data Transaction data payload.
-    /// @return Returns transaction ID.
-    fn add_transaction(&mut self, destination:&ActorId, value:&uint, data:&[u8])->uint
+    /// @dev Allows to lift the pause, which needs the full quorum. Transaction has to be sent by wallet.
+    pub fn unpause(&mut self)
     {
-        Self::not_null(destination);
-        let transaction_id = self.transaction_count;
-        self.transactions.insert(transaction_id, Transaction{
-            destination:destination.clone(),
-            value: *value,
-            data: data.to_vec(),
-            executed: false
-        });
-        self.transaction_count += 1;
-        // self.Submission(transactionId);
-        transaction_id
+        self.only_wallet();
+        self.pause_votes.clear();
+        self.paused_until = None;
+        // Unpause();
     }
 
-    /*
-     * Web3 call functions
-     */
-    /// @dev Returns number of confirmations of a transaction.
-    /// @param transactionId Transaction ID.
-    /// @return Number of confirmations.
-    pub fn get_confirmation_count(&self, transaction_id:&uint)->uint
+    /// @dev Allows to change how many owners can pause and how long a pause lasts at most.
+    /// Transaction has to be sent by wallet.
+    /// @param threshold Number of owner votes pausing the wallet.
+    /// @param maxDuration Number of blocks after which the pause ends by itself.
+    pub fn change_pause_config(&mut self, threshold:uint, max_duration:u32)
     {
-        let cc = match self.confirmations.get(transaction_id) {
-            Some(dict) => {
-                dict.values().fold(0 as uint, |n, value|if *value {n + 1} else {n})
-            },
-            None => 0
-        };
-        cc
+        self.only_wallet();
+        assert!(threshold > 0 && threshold <= self.owners.len() as uint, "invalid pause threshold");
+        assert!(max_duration > 0, "pause must last at least one block");
+        self.pause_threshold = threshold;
+        self.max_pause_duration = max_duration;
+        // PauseConfigChange(threshold, maxDuration);
     }
 
-    /// @dev Returns total number of transactions after filers are applied.
-    /// @param pending Include pending transactions.
-    /// @param executed Include executed transactions.
-    /// @return Total number of transactions after filters are applied.
-    pub fn get_transaction_count(&self, pending:bool, executed: bool)->uint
+    /// @dev Returns whether the wallet is paused, a pause ends by itself after its maximum duration.
+    pub fn is_paused(&self)->bool
     {
-        self.transactions.values().fold(0, |n, transaction| if pending && !transaction.executed || executed && transaction.executed {n + 1} else {n})
+        self.paused_until.map_or(false, |until|exec::block_height() < until)
     }
 
-    /// @dev Returns list of owners.
-    /// @return List of owner addresses.
-    pub fn get_owners(&self) -> Vec<ActorId>
+    /// @dev Allows to enable a module program which can execute calls without confirmations.
+    /// Transaction has to be sent by wallet.
+    /// @param module Address of the module program.
+    pub fn enable_module(&mut self, module:&ActorId)
     {
-        self.owners.clone()
+        self.only_wallet();
+        Self::not_null(module);
+        assert!(*module != exec::program_id(), "wallet can't be its own module");
+        assert!(self.modules.insert(*module), "module already enabled");
+        // ModuleEnabled(module);
     }
 
-    /// @dev Returns array with owner addresses, which confirmed transaction.
-    /// @param transactionId Transaction ID.
-    /// @return Returns array of owner addresses.
-    pub fn get_confirmations(&self, transaction_id:&uint) -> Vec<ActorId> 
+    /// @dev Allows to disable a module program. Transaction has to be sent by wallet.
+    /// @param module Address of the module program.
+    pub fn disable_module(&mut self, module:&ActorId)
     {
-        let confirmations = match self.confirmations.get(transaction_id) {
-            Some(dict) => {
-                self.owners.iter().take_while(|owner| *dict.get(owner).unwrap_or(&false)).cloned().collect()
-            },
-            None => vec![],
-        };
-        confirmations
+        self.only_wallet();
+        assert!(self.modules.remove(module), "module not enabled");
+        // ModuleDisabled(module);
     }
 
-    /// @dev Returns list of transaction IDs in defined range.
-    /// @param from Index start position of transaction array.
-    /// @param to Index end position of transaction array.
-    /// @param pending Include pending transactions.
-    /// @param executed Include executed transactions.
-    /// @return Returns array of transaction IDs.
-    pub fn get_transaction_ids(&self, from:&uint, to:&uint, pending:bool, executed:bool)->Vec<uint>
-    {
-        let ids:Vec<uint> = self.transactions.iter()
-        .take_while(|e|e.0 >= from && e.0 < to)
-        .take_while(|e|(pending && !e.1.executed)||(executed && e.1.executed))
-        .map(|e|e.0).cloned().collect();
-        return ids;
+    /// @dev Allows an enabled module to execute a call without confirmations.
+    /// The outcome is logged and sent to the module once the destination replies.
+    /// @param destination Call target address.
+    /// @param value Call value.
+    /// @param data Call data payload.
+    /// @return Returns the module call ID.
+    pub fn exec_from_module(&mut self, sender:&ActorId, destination:&ActorId, value:&uint, data:&[u8])->uint
+    {
+        assert!(self.modules.contains(sender), "{:?} is not an enabled module", sender);
+        Self::not_null(destination);
+        // governance needs confirmations, a module can't bypass them
+        assert!(*destination != exec::program_id(), "modules can't call the wallet itself");
+        assert!(!self.is_paused(), "wallet is paused");
+        let call_id = self.module_call_count;
+        self.module_call_count += 1;
+        self.module_calls.insert(call_id, ModuleCall{module:*sender, destination:*destination, value:*value, data:data.to_vec()});
+        self.advance_module(&call_id, ExecutionStage::ModuleGuard);
+        call_id
+    }
+
+    // Runs the call of a module from `stage` on, the guard approves it like the call of a transaction.
+    fn advance_module(&mut self, call_id:&uint, stage:ExecutionStage)->ExecutionResult
+    {
+        let call = match self.module_calls.get(call_id) {
+            Some(call) => call,
+            None => return ExecutionResult::NotExecuted,
+        };
+        let (module, destination, value) = (call.module, call.destination, call.value);
+        if stage <= ExecutionStage::ModuleGuard {
+            if let Some(guard) = self.guard_for(&destination) {
+                let check = GuardAction::CheckModuleCall{module, destination, value, data:call.data.clone()};
+                let sent = msg::send(guard, check, 0).map_err(|e|format!("guard: {:?}", e));
+                return self.dispatch(call_id, ExecutionStage::ModuleGuard, sent);
+            }
+        }
+        if self.is_paused() {
+            // the wallet may have been paused while the guard reply was awaited
+            return self.settle_module(call_id, Err(String::from("wallet is paused")));
+        }
+        let data = core::mem::take(&mut self.module_calls.get_mut(call_id).unwrap().data);
+        let sent = msg::send_bytes(destination, data, value).map_err(|e|format!("{:?}", e));
+        self.dispatch(call_id, ExecutionStage::Module, sent)
+    }
+
+    fn settle_module(&mut self, call_id:&uint, reply:Result<Vec<u8>, String>)->ExecutionResult
+    {
+        let call = match self.module_calls.remove(call_id) {
+            Some(call) => call,
+            None => return ExecutionResult::NotExecuted,
+        };
+        let failure = reply.err();
+        let result = if failure.is_none() {ExecutionResult::Executed} else {ExecutionResult::Failed};
+        let event = Event::ExecutionFromModule{module:call.module, destination:call.destination, value:call.value, failure};
+        self.log_event(&call.module, event.clone());
+        // the ExecFromModule message of the module has been answered already
+        let _ = msg::send(call.module, event, 0);
+        result
+    }
+
+    /// @dev Allows to register a program notified about state changes, or update its filter.
+    /// Transaction has to be sent by wallet.
+    /// @param listener Address of the listener program.
+    /// @param filter Notifications the listener wants to receive.
+    pub fn add_listener(&mut self, listener:&ActorId, filter:NotificationFilter)
+    {
+        self.only_wallet();
+        Self::not_null(listener);
+        assert!(self.listeners.contains_key(listener) || self.listeners.len() < MAX_LISTENER_COUNT, "too many listeners");
+        self.listeners.insert(*listener, filter);
+        // ListenerAdded(listener, filter);
+    }
+
+    /// @dev Allows to unregister a listener program. Transaction has to be sent by wallet.
+    /// @param listener Address of the listener program.
+    pub fn remove_listener(&mut self, listener:&ActorId)
+    {
+        self.only_wallet();
+        assert!(self.listeners.remove(listener).is_some(), "not a listener");
+        // ListenerRemoved(listener);
+    }
+
+    /// @dev Allows an owner to submit and confirm a transaction.
+    /// @param txn Destination, value, data and execution rules of the transaction.
+    /// @param bond Value attached by the submitter, the configured bond is refunded on execution or cancellation
+    /// by the submitter, any excess is kept as a deposit.
+    /// @return Returns transaction ID.
+    pub fn submit_transaction(&mut self, sender:&ActorId, txn:NewTransaction, bond:uint)->uint
+    {
+        self.submit(sender, txn, bond, false)
+    }
+
+    fn submit(&mut self, sender:&ActorId, mut txn:NewTransaction, bond:uint, wallet_vote:bool)->uint
+    {
+        self.owner_exists(sender);
+        self.when_not_paused(&txn.destination);
+        txn.details.validate();
+        if let Some(condition) = &txn.condition {
+            condition.validate();
+        }
+        assert!(bond >= self.bond, "a bond of {} is required", self.bond);
+        let deposit = bond - self.bond;
+        let bond = self.bond;
+        self.valid_dependencies(&txn.depends_on);
+        txn.nonce = if self.nonce_mode {
+            let next_free = self.next_free_nonce();
+            let nonce = txn.nonce.unwrap_or(next_free);
+            assert!(nonce >= self.next_nonce && nonce <= next_free, "nonce {} is out of range {}..={}", nonce, self.next_nonce, next_free);
+            Some(nonce)
+        } else {
+            assert!(txn.nonce.is_none(), "nonces are only used in strict order mode");
+            None
+        };
+        let transaction_id = self.add_transaction(sender, txn, bond, wallet_vote);
+        if deposit > 0 {
+            self.log_event(sender, Event::Deposit{sender:*sender, value:deposit});
+        }
+        self.confirm_transaction(sender, &transaction_id, None);
+        transaction_id
+    }
+
+    fn valid_dependencies(&self, depends_on:&[uint])
+    {
+        for (i, dependency) in depends_on.iter().enumerate() {
+            // ids follow the submission order, depending only on earlier transactions rules out any cycle
+            assert!(*dependency < self.transaction_count, "dependency {} would make a cycle", dependency);
+            assert!(!depends_on[..i].contains(dependency), "duplicate dependency {}", dependency);
+            let status = self.status_of(dependency).expect("dependency does not exist");
+            assert!(status != TransactionStatus::Cancelled, "dependency {} is cancelled", dependency);
+        }
+    }
+
+    // Status of a transaction, kept apart from the archive once it is pruned.
+    fn status_of(&self, transaction_id:&uint) -> Option<TransactionStatus>
+    {
+        match self.transactions.get(transaction_id) {
+            Some(txn) => Some(txn.status),
+            None => self.pruned.get(transaction_id).cloned(),
+        }
+    }
+
+    fn is_ready(&self, transaction_id:&uint) -> bool
+    {
+        let txn = &self.transactions[transaction_id];
+        let in_order = match txn.nonce {
+            // competing transactions don't run at the same time, the first to finish invalidates the others
+            Some(nonce) if self.nonce_mode => nonce == self.next_nonce
+                && self.nonces[&nonce].iter().all(|id|!matches!(self.status_of(id), Some(TransactionStatus::Executing | TransactionStatus::Scheduled))),
+            _ => true,
+        };
+        in_order && txn.depends_on.iter()
+            .all(|dependency|self.status_of(dependency) == Some(TransactionStatus::Executed))
+    }
+
+    // Nonce given to a transaction submitted without one, following the highest nonce in use.
+    fn next_free_nonce(&self) -> u64
+    {
+        self.nonces.keys().next_back().map_or(self.next_nonce, |nonce|nonce + 1).max(self.next_nonce)
+    }
+
+    // Cancels the transactions competing for the nonce of an executed transaction, their bonds are refunded.
+    fn invalidate_competitors(&mut self, transaction_id:&uint)
+    {
+        // a transaction in flight when strict order was turned off still uses up its nonce
+        let nonce = match self.transactions[transaction_id].nonce {
+            Some(nonce) => nonce,
+            None => return,
+        };
+        self.next_nonce = nonce + 1;
+        for id in self.nonces.remove(&nonce).unwrap_or_default() {
+            if id != *transaction_id && self.transactions.get(&id).map_or(false, |txn|txn.status == TransactionStatus::Pending) {
+                self.cancel(&id);
+                self.refund_bond(&id);
+            }
+        }
+    }
+
+    /// @dev Allows an owner to propose confirming or revoking a transaction in another wallet this wallet owns.
+    /// @param wallet Address of the child wallet.
+    /// @param transactionId Transaction ID in the child wallet.
+    /// @param confirm Confirm the child transaction if true, revoke the confirmation otherwise.
+    /// @param bond Value attached by the submitter.
+    /// @return Returns transaction ID.
+    pub fn submit_wallet_vote(&mut self, sender:&ActorId, wallet:&ActorId, transaction_id:&uint, confirm:bool, bond:uint)->uint
+    {
+        assert!(*wallet != exec::program_id(), "wallet can't vote in itself");
+        // a wallet owning this one and owned by it would make a cycle
+        self.owner_does_not_exist(wallet);
+        let action = if confirm {
+            Action::ConfirmTransaction{transaction_id:*transaction_id, comment:None}
+        } else {
+            Action::RevokeConfirmation{transaction_id:*transaction_id, comment:None}
+        };
+        let txn = NewTransaction{
+            destination: *wallet,
+            value: 0,
+            data: action.encode(),
+            gas_limit: None,
+            details: ProposalDetails::default(),
+            depends_on: vec![],
+            nonce: None,
+            condition: None,
+        };
+        self.submit(sender, txn, bond, true)
+    }
+
+    // A vote which went through in another wallet shows this wallet is one of its voters.
+    fn record_child_wallet(&mut self, transaction_id:&uint)
+    {
+        let txn = &self.transactions[transaction_id];
+        if txn.wallet_vote {
+            self.child_wallets.insert(txn.destination);
+        }
+    }
+
+    /// @dev Allows to forget a wallet this wallet voted in, once it no longer owns it. Transaction has to be sent by wallet.
+    /// @param wallet Address of the child wallet.
+    pub fn remove_child_wallet(&mut self, wallet:&ActorId)
+    {
+        self.only_wallet();
+        assert!(self.child_wallets.remove(wallet), "not a child wallet");
+        // ChildWalletRemoval(wallet);
+    }
+
+    /// @dev Allows the submitter to amend the description while nobody else confirmed the transaction.
+    /// @param transactionId Transaction ID.
+    /// @param description New description.
+    pub fn amend_description(&mut self, sender:&ActorId, transaction_id:&uint, description:Option<String>)
+    {
+        self.not_executing(transaction_id);
+        self.not_executed(transaction_id);
+        self.not_cancelled(transaction_id);
+        ProposalDetails::validate_description(&description);
+        let others_confirmed = self.confirmations.get(transaction_id)
+            .map_or(false, |dict|dict.iter().any(|(owner, confirmed)|*confirmed && owner != sender));
+        assert!(!others_confirmed, "transaction is already confirmed by other owners");
+        let txn = self.transactions.get_mut(transaction_id).expect("transaction does not exist");
+        assert!(txn.submitter == *sender, "only the submitter can amend the description");
+        txn.details.description = description;
+        // DescriptionAmendment(transactionId);
+    }
+
+    /// @dev Allows an owner, or its delegate, to confirm a transaction.
+    /// @param transactionId Transaction ID.
+    /// @param comment Optional short reason kept in the confirmation history.
+    pub fn confirm_transaction(&mut self, sender:&ActorId, transaction_id:&uint, comment:Option<String>)->ExecutionResult
+    {
+        let owner = self.acting_owner(sender);
+        let delegate = Some(*sender).filter(|sender|*sender != owner);
+        let sender = &owner;
+        self.voter_exists(transaction_id, sender);
+        // self.transaction_exists(transaction_id);
+        assert!(self.transactions.get(transaction_id).unwrap().destination != ZERO_ID);
+        self.not_executing(transaction_id);
+        self.not_executed(transaction_id);
+        self.not_cancelled(transaction_id);
+        self.not_expired(transaction_id);
+
+        self.not_confirmed(transaction_id, sender);
+        self.confirmations.entry(transaction_id.clone()).or_insert_with(||BTreeMap::new()).entry(sender.clone()).and_modify(|e| *e = true).or_insert(true);
+        if let Some(rejected) = self.rejections.get_mut(transaction_id) {
+            rejected.remove(sender);
+        }
+        self.record_confirmation(sender, delegate, transaction_id, ConfirmationAction::Confirm, comment);
+        // Confirmation(msg.sender, transaction_id);
+        let destination = self.transactions[transaction_id].destination;
+        if self.is_paused() && destination != exec::program_id() {
+            // confirmations are still collected, the execution waits for the unpause
+            return ExecutionResult::NotExecuted;
+        }
+        if !self.is_ready(transaction_id) {
+            // executed by the cascade once the last dependency is
+            return ExecutionResult::NotExecuted;
+        }
+        if self.transactions[transaction_id].failure_count > self.max_retries {
+            // the confirmation still counts, the execution attempts are used up
+            return ExecutionResult::NotExecuted;
+        }
+        if self.is_confirmed(transaction_id) && self.schedule_execution(transaction_id) {
+            return ExecutionResult::Scheduled;
+        }
+        self.execute_transaction(sender, transaction_id)
+    }
+
+    /// @dev Allows an owner, or its delegate, to revoke a confirmation for a transaction.
+    /// @param transactionId Transaction ID.
+    /// @param comment Optional short reason kept in the confirmation history.
+    pub fn revoke_confirmation(&mut self, sender:&ActorId, transaction_id:&uint, comment:Option<String>)
+    {
+        let owner = self.acting_owner(sender);
+        let delegate = Some(*sender).filter(|sender|*sender != owner);
+        let sender = &owner;
+        self.voter_exists(transaction_id, sender);
+        self.confirmed(transaction_id, sender);
+        self.not_executing(transaction_id);
+        self.not_executed(transaction_id);
+        // self.confirmations.entry[transactionId][msg.sender] = false;
+        self.confirmations.entry(*transaction_id).or_insert_with(||BTreeMap::new()).entry(*sender).and_modify(|e|*e = false).or_insert(false);
+        self.record_confirmation(sender, delegate, transaction_id, ConfirmationAction::Revoke, comment);
+        // Revocation(msg.sender, transaction_id);
+    }
+
+    /// @dev Allows an owner to vote against a transaction, replacing its confirmation if any.
+    /// The transaction is cancelled once the remaining owners can't reach the required confirmations.
+    /// @param transactionId Transaction ID.
+    /// @param comment Optional short reason kept in the confirmation history.
+    /// @return Returns true if the transaction has been cancelled.
+    pub fn reject_transaction(&mut self, sender:&ActorId, transaction_id:&uint, comment:Option<String>)->bool
+    {
+        assert!(self.transactions.contains_key(transaction_id), "transaction does not exist");
+        self.voter_exists(transaction_id, sender);
+        self.not_executing(transaction_id);
+        self.not_executed(transaction_id);
+        self.not_cancelled(transaction_id);
+        assert!(self.rejections.entry(*transaction_id).or_default().insert(*sender), "transaction already rejected");
+        if let Some(confirmed) = self.confirmations.get_mut(transaction_id).and_then(|dict|dict.get_mut(sender)) {
+            *confirmed = false;
+        }
+        self.record_confirmation(sender, None, transaction_id, ConfirmationAction::Reject, comment);
+        // Rejection(msg.sender, transaction_id);
+
+        let rejected = &self.rejections[transaction_id];
+        let voters = self.voters(transaction_id);
+        let rejections = voters.iter().filter(|owner|rejected.contains(owner)).count() as uint;
+        if (voters.len() as uint) - rejections < self.required_for(transaction_id) {
+            self.cancel(transaction_id);
+            self.forfeit_bond(transaction_id);
+            return true;
+        }
+        false
+    }
+
+    fn cancel(&mut self, transaction_id:&uint)
+    {
+        self.transactions.get_mut(transaction_id).unwrap().status = TransactionStatus::Cancelled;
+        self.pending_ids.remove(transaction_id);
+        self.cancelled_ids.insert(*transaction_id);
+        self.log_event(&msg::source(), Event::Cancellation{transaction_id:*transaction_id});
+        // a dependent can't become ready anymore, it is withdrawn with it and its submitter refunded
+        let dependents:Vec<uint> = self.dependents.get(transaction_id).into_iter().flatten().cloned().collect();
+        for id in dependents {
+            if self.transactions.get(&id).map_or(false, |txn|txn.status == TransactionStatus::Pending) {
+                self.cancel(&id);
+                self.refund_bond(&id);
+            }
+        }
+    }
+
+    /// @dev Allows the submitter to withdraw a transaction, its bond is refunded.
+    /// @param transactionId Transaction ID.
+    pub fn cancel_transaction(&mut self, sender:&ActorId, transaction_id:&uint)
+    {
+        let txn = self.transactions.get(transaction_id).expect("transaction does not exist");
+        assert!(txn.submitter == *sender, "only the submitter can cancel the transaction");
+        self.not_executing(transaction_id);
+        self.not_executed(transaction_id);
+        self.not_cancelled(transaction_id);
+        self.cancel(transaction_id);
+        self.refund_bond(transaction_id);
+    }
+
+    /// @dev Allows anyone to cancel a transaction which expired unexecuted, its bond is forfeited to the wallet.
+    /// @param transactionId Transaction ID.
+    pub fn expire_transaction(&mut self, transaction_id:&uint)
+    {
+        let expires_at = self.transactions.get(transaction_id).expect("transaction does not exist").expires_at;
+        assert!(expires_at.map_or(false, |expires_at|exec::block_height() >= expires_at), "transaction {} has not expired", transaction_id);
+        self.not_executing(transaction_id);
+        self.not_executed(transaction_id);
+        self.not_cancelled(transaction_id);
+        self.cancel(transaction_id);
+        self.forfeit_bond(transaction_id);
+    }
+
+    /// @dev Allows the submitter to claim a bond whose refund failed.
+    /// @param transactionId Transaction ID.
+    /// @return Returns the refunded amount.
+    pub fn claim_bond(&mut self, sender:&ActorId, transaction_id:&uint)->uint
+    {
+        let txn = self.transactions.get(transaction_id).expect("transaction does not exist");
+        assert!(txn.submitter == *sender, "only the submitter can claim the bond");
+        // forfeiting clears the bond, what is left on a closed transaction is owed to the submitter
+        assert!(matches!(txn.status, TransactionStatus::Executed | TransactionStatus::Cancelled), "transaction {} is still open", transaction_id);
+        let amount = txn.bond;
+        assert!(amount > 0, "no bond to claim");
+        assert!(self.refund_bond(transaction_id), "unable to refund the bond");
+        amount
+    }
+
+    // Never panics as it runs in `handle_reply`, a failed refund keeps the bond claimable.
+    fn refund_bond(&mut self, transaction_id:&uint)->bool
+    {
+        let (submitter, amount) = match self.transactions.get(transaction_id) {
+            Some(txn) => (txn.submitter, txn.bond),
+            None => return false,
+        };
+        if amount == 0 {
+            return true;
+        }
+        match msg::send_bytes(submitter, b"", amount) {
+            Ok(_) => {
+                self.transactions.get_mut(transaction_id).unwrap().bond = 0;
+                self.log_event(&msg::source(), Event::BondRefund{transaction_id:*transaction_id, submitter, amount});
+                true
+            },
+            Err(e) => {
+                self.log_event(&msg::source(), Event::BondRefundFailure{transaction_id:*transaction_id, amount, reason:format!("{:?}", e)});
+                false
+            },
+        }
+    }
+
+    // the value stays in the wallet balance
+    fn forfeit_bond(&mut self, transaction_id:&uint)
+    {
+        let amount = core::mem::take(&mut self.transactions.get_mut(transaction_id).unwrap().bond);
+        if amount > 0 {
+            self.log_event(&msg::source(), Event::BondForfeit{transaction_id:*transaction_id, amount});
+        }
+    }
+
+    // `delegate` is the account which acted for the owner, if any.
+    fn record_confirmation(&mut self, owner:&ActorId, delegate:Option<ActorId>, transaction_id:&uint, action:ConfirmationAction, comment:Option<String>)
+    {
+        if let Some(comment) = &comment {
+            assert!(comment.len() <= MAX_COMMENT_LENGTH, "comment is longer than {} bytes", MAX_COMMENT_LENGTH);
+        }
+        self.confirmation_history.entry(*transaction_id).or_default().push(ConfirmationRecord{
+            owner: *owner,
+            delegate,
+            action,
+            block: exec::block_height(),
+            timestamp: exec::block_timestamp(),
+            comment,
+        });
+    }
+
+    /// @dev Allows anyone to execute a confirmed transaction.
+    /// @param transactionId Transaction ID.
+    pub fn execute_transaction(&mut self, sender:&ActorId, transaction_id:&uint)->ExecutionResult
+    {
+        self.voter_exists(transaction_id, sender);
+        self.confirmed(transaction_id, sender);
+        self.not_executing(transaction_id);
+        self.not_executed(transaction_id);
+        self.when_not_paused(&self.transactions[transaction_id].destination);
+        self.retries_left(transaction_id);
+        self.ready(transaction_id);
+        self.perform_transaction(transaction_id)
+    }
+
+    /// @dev Allows any owner to execute again a confirmed transaction whose execution failed.
+    /// @param transactionId Transaction ID.
+    pub fn retry_transaction(&mut self, sender:&ActorId, transaction_id:&uint)->ExecutionResult
+    {
+        self.owner_exists(sender);
+        self.not_executing(transaction_id);
+        self.not_executed(transaction_id);
+        assert!(self.transactions.get(transaction_id).expect("transaction does not exist").failure_count > 0, "transaction has not failed");
+        self.when_not_paused(&self.transactions[transaction_id].destination);
+        self.retries_left(transaction_id);
+        self.ready(transaction_id);
+        self.perform_transaction(transaction_id)
+    }
+
+    /// @dev Executes a confirmed transaction scheduled by the wallet itself, paid from the gas reservation pool.
+    /// Transaction has to be sent by wallet.
+    /// @param transactionId Transaction ID.
+    pub fn execute_scheduled(&mut self, transaction_id:&uint)->ExecutionResult
+    {
+        self.only_wallet();
+        if self.in_flight.remove(&msg::id()).is_none() {
+            // not sent by schedule_execution, or aborted meanwhile
+            return ExecutionResult::NotExecuted;
+        }
+        self.transactions.get_mut(transaction_id).unwrap().status = TransactionStatus::Pending;
+        // the reservation is spent already, a transaction which can't run anymore is left pending
+        if !self.can_cascade(transaction_id) {
+            return ExecutionResult::NotExecuted;
+        }
+        self.perform_transaction(transaction_id)
+    }
+
+    // Starts the execution of every dependent and next nonce an executed transaction unblocked.
+    fn cascade(&mut self, transaction_id:&uint)
+    {
+        let mut ready:BTreeSet<uint> = self.dependents.get(transaction_id).cloned().unwrap_or_default();
+        if self.nonce_mode {
+            ready.extend(self.nonces.get(&self.next_nonce).into_iter().flatten());
+        }
+        // in id order, a started execution makes its nonce competitors wait
+        for id in ready {
+            if self.can_cascade(&id) {
+                self.perform_transaction(&id);
+            }
+        }
+    }
+
+    // A dependent is left for an explicit execution whenever perform_transaction would refuse it.
+    fn can_cascade(&self, transaction_id:&uint) -> bool
+    {
+        let txn = match self.transactions.get(transaction_id) {
+            Some(txn) => txn,
+            None => return false,
+        };
+        txn.status == TransactionStatus::Pending
+            && txn.failure_count <= self.max_retries
+            && txn.expires_at.map_or(true, |expires_at|exec::block_height() < expires_at)
+            && (!self.is_paused() || txn.destination == exec::program_id())
+            && self.is_ready(transaction_id)
+            && self.is_confirmed(transaction_id)
+    }
+
+    // Starts the execution of a confirmed transaction, its outcome is settled by the replies in `handle_reply`.
+    fn perform_transaction(&mut self, transaction_id:&uint)->ExecutionResult
+    {
+        self.not_cancelled(transaction_id);
+        self.not_expired(transaction_id);
+        if self.is_confirmed(transaction_id) {
+            let txn = self.transactions.get_mut(transaction_id).unwrap();
+            // any message reaching the wallet until the last reply sees the transaction in flight
+            txn.status = TransactionStatus::Executing;
+            return self.advance(transaction_id, ExecutionStage::Condition);
+        }
+        ExecutionResult::NotExecuted
+    }
+
+    // Runs the execution from `stage` on until it has to wait for a reply.
+    fn advance(&mut self, transaction_id:&uint, stage:ExecutionStage)->ExecutionResult
+    {
+        let txn = &self.transactions[transaction_id];
+        let (destination, value, data, gas_limit, condition) = (txn.destination, txn.value, txn.data.clone(), txn.gas_limit, txn.condition.clone());
+        if stage <= ExecutionStage::Condition {
+            match condition {
+                Some(Condition::Query{program, payload, ..}) => {
+                    let sent = msg::send_bytes(program, payload, 0).map_err(|e|format!("condition: {:?}", e));
+                    return self.dispatch(transaction_id, ExecutionStage::Condition, sent);
+                },
+                Some(Condition::BlockRange{from, to}) => {
+                    let now = exec::block_height();
+                    if now < from || now > to {
+                        return self.settle(transaction_id, ExecutionStage::Condition, Err(format!("condition: block {} is out of {}..={}", now, from, to)));
+                    }
+                },
+                None => {},
+            }
+        }
+        if stage <= ExecutionStage::Guard {
+            if let Some(guard) = self.guard_for(&destination) {
+                let check = GuardAction::CheckTransaction{transaction_id:*transaction_id, destination, value, data:data.clone(), gas_limit};
+                let sent = msg::send(guard, check, 0).map_err(|e|format!("guard: {:?}", e));
+                return self.dispatch(transaction_id, ExecutionStage::Guard, sent);
+            }
+        }
+        if self.is_paused() && destination != exec::program_id() {
+            // the wallet may have been paused while a condition or guard reply was awaited
+            self.interrupt(transaction_id, String::from("wallet is paused"));
+            return ExecutionResult::NotExecuted;
+        }
+        let sent = match gas_limit {
+            Some(gas_limit) => msg::send_bytes_with_gas(destination, data, gas_limit, value),
+            None => msg::send_bytes(destination, data, value),
+        };
+        self.dispatch(transaction_id, ExecutionStage::Call, sent.map_err(|e|format!("{:?}", e)))
+    }
+
+    fn dispatch(&mut self, transaction_id:&uint, stage:ExecutionStage, sent:Result<MessageId, String>)->ExecutionResult
+    {
+        match sent {
+            Ok(message_id) => {
+                self.in_flight.insert(message_id, (*transaction_id, stage, exec::block_height()));
+                ExecutionResult::Dispatched
+            },
+            // a message which can't be sent settles the stage like a failed reply
+            Err(reason) => self.settle(transaction_id, stage, Err(reason)),
+        }
+    }
+
+    /// @dev Settles the execution step a reply answers, replies to other messages are ignored.
+    /// @param replyTo Message the reply answers.
+    /// @param reply Reply payload, or the reason the message failed.
+    pub fn handle_reply(&mut self, reply_to:&MessageId, reply:Result<Vec<u8>, String>)
+    {
+        if let Some((transaction_id, stage, _)) = self.in_flight.remove(reply_to) {
+            self.settle(&transaction_id, stage, reply);
+        }
+    }
+
+    /// @dev Allows to abort an execution waiting for a reply which may never come, a reply arriving later is ignored.
+    /// Transaction has to be sent by wallet, or by an owner once the reply is EXECUTION_TIMEOUT blocks overdue.
+    /// An aborted call counts as a failed execution, an aborted schedule, condition or guard check puts the transaction back to pending.
+    /// @param transactionId Transaction ID.
+    pub fn abort_execution(&mut self, sender:&ActorId, transaction_id:&uint)
+    {
+        // the post-check and module calls don't hold a transaction
+        let (message_id, stage, sent_at) = self.in_flight.iter()
+            .find(|(_, (id, stage, _))|id == transaction_id && (*stage <= ExecutionStage::Call || *stage == ExecutionStage::Scheduled))
+            .map(|(message_id, (_, stage, sent_at))|(*message_id, *stage, *sent_at))
+            .expect("no execution in flight");
+        if *sender != exec::program_id() {
+            // in strict order mode the wallet can't approve anything while the next nonce is stuck
+            self.owner_exists(sender);
+            assert!(exec::block_height() >= sent_at.saturating_add(EXECUTION_TIMEOUT), "reply is not overdue before block {}", sent_at.saturating_add(EXECUTION_TIMEOUT));
+        }
+        self.in_flight.remove(&message_id);
+        let reason = String::from("aborted");
+        if stage == ExecutionStage::Call {
+            self.settle(transaction_id, stage, Err(reason));
+        } else {
+            self.interrupt(transaction_id, reason);
+        }
+        // ExecutionAborted(transactionId);
+    }
+
+    // Settles a step with its reply and goes on with the next one.
+    fn settle(&mut self, transaction_id:&uint, stage:ExecutionStage, reply:Result<Vec<u8>, String>)->ExecutionResult
+    {
+        match stage {
+            ExecutionStage::Condition => {
+                let checked = match &self.transactions[transaction_id].condition {
+                    Some(Condition::Query{comparator, expected, ..}) => reply.and_then(|reply|Condition::compare(*comparator, expected, &reply)),
+                    _ => reply.map(|_|()),
+                };
+                match checked {
+                    Ok(()) => self.advance(transaction_id, ExecutionStage::Guard),
+                    // not a failure, the transaction simply waits until the condition holds
+                    Err(reason) => {
+                        self.interrupt(transaction_id, reason.clone());
+                        self.log_event(&msg::source(), Event::ConditionNotMet{transaction_id:*transaction_id, reason});
+                        ExecutionResult::ConditionNotMet
+                    },
+                }
+            },
+            ExecutionStage::Guard => match Self::guard_verdict(reply) {
+                Ok(()) => self.advance(transaction_id, ExecutionStage::Call),
+                Err(reason) => {
+                    self.interrupt(transaction_id, reason.clone());
+                    self.log_event(&msg::source(), Event::GuardRejection{transaction_id:*transaction_id, reason});
+                    ExecutionResult::GuardRejected
+                },
+            },
+            ExecutionStage::Call => {
+                let success = reply.is_ok();
+                let txn = self.transactions.get_mut(transaction_id).unwrap();
+                match reply {
+                    Ok(_) => {
+                        txn.status = TransactionStatus::Executed;
+                        self.pending_ids.remove(transaction_id);
+                        self.executed_ids.insert(*transaction_id);
+                        self.executed_count += 1;
+                        self.log_event(&msg::source(), Event::Execution{transaction_id:*transaction_id});
+                        self.record_child_wallet(transaction_id);
+                        self.refund_bond(transaction_id);
+                        self.invalidate_competitors(transaction_id);
+                    },
+                    Err(reason) => {
+                        txn.status = TransactionStatus::Pending;
+                        txn.failure_count += 1;
+                        txn.last_failure = Some(reason.clone());
+                        self.log_event(&msg::source(), Event::ExecutionFailure{transaction_id:*transaction_id, reason});
+                    },
+                }
+                // the call can't be undone anymore, a rejection of the post-check is only recorded
+                if let Some(guard) = self.guard_for(&self.transactions[transaction_id].destination) {
+                    let check = GuardAction::CheckAfterExecution{transaction_id:*transaction_id, success};
+                    if let Ok(message_id) = msg::send(guard, check, 0) {
+                        self.in_flight.insert(message_id, (*transaction_id, ExecutionStage::AfterCheck, exec::block_height()));
+                    }
+                }
+                if success {
+                    self.cascade(transaction_id);
+                }
+                if success {ExecutionResult::Executed} else {ExecutionResult::Failed}
+            },
+            ExecutionStage::AfterCheck => {
+                if let Err(reason) = Self::guard_verdict(reply) {
+                    self.log_event(&msg::source(), Event::GuardRejection{transaction_id:*transaction_id, reason});
+                }
+                // the transaction may be pruned by now
+                match self.transactions.get(transaction_id) {
+                    Some(txn) if txn.status == TransactionStatus::Executed => ExecutionResult::Executed,
+                    _ => ExecutionResult::Failed,
+                }
+            },
+            ExecutionStage::ModuleGuard => match Self::guard_verdict(reply) {
+                Ok(()) => self.advance_module(transaction_id, ExecutionStage::Module),
+                Err(reason) => self.settle_module(transaction_id, Err(reason)),
+            },
+            ExecutionStage::Module => self.settle_module(transaction_id, reply),
+            ExecutionStage::AllowanceGuard => self.settle_allowance_spend(transaction_id, Self::guard_verdict(reply)),
+            ExecutionStage::Scheduled => {
+                // the scheduled message failed before it got to the transaction
+                let reason = reply.err().unwrap_or_default();
+                self.interrupt(transaction_id, format!("scheduled: {}", reason));
+                ExecutionResult::NotExecuted
+            },
+        }
+    }
+
+    // Puts a transaction whose execution stopped before the call back to pending.
+    fn interrupt(&mut self, transaction_id:&uint, reason:String)
+    {
+        let txn = self.transactions.get_mut(transaction_id).unwrap();
+        txn.status = TransactionStatus::Pending;
+        txn.last_failure = Some(reason);
+    }
+
+    fn guard_for(&self, destination:&ActorId) -> Option<ActorId>
+    {
+        match self.guard {
+            Some(guard) if *destination != exec::program_id() => Some(guard),
+            _ => None,
+        }
+    }
+
+    fn guard_verdict(reply:Result<Vec<u8>, String>) -> Result<(), String> {
+        let reply = reply.map_err(|e|format!("guard: {}", e))?;
+        match GuardReply::decode(&mut reply.as_ref()) {
+            Ok(GuardReply::Approved) => Ok(()),
+            Ok(GuardReply::Rejected{reason}) => Err(format!("guard: {}", reason)),
+            Err(e) => Err(format!("guard: {:?}", e)),
+        }
+    }
+
+    /// @dev Sends the execution of a confirmed transaction to the wallet itself, paid from the gas reservation pool.
+    /// @param transactionId Transaction ID.
+    /// The transaction is marked scheduled, so it can't be executed or revoked before the message arrives.
+    /// @return Returns true if a reservation was used, false if the pool can't cover the execution.
+    fn schedule_execution(&mut self, transaction_id:&uint)->bool
+    {
+        self.drop_expired_reservations();
+        // the wallet has to handle the message on top of the gas forwarded with the call
+        let needed = self.transactions.get(transaction_id).unwrap().gas_limit.unwrap_or(0).saturating_add(SCHEDULED_EXECUTION_GAS);
+        let idx = match self.reservations.iter().position(|r|r.amount >= needed) {
+            Some(idx) => idx,
+            None => return false,
+        };
+        let action = Action::ExecuteScheduled{transaction_id:*transaction_id};
+        let message_id = match msg::send_from_reservation(self.reservations[idx].id, exec::program_id(), action, 0) {
+            Ok(message_id) => message_id,
+            // the reservation is kept, the execution is paid by the confirmation instead
+            Err(_) => return false,
+        };
+        self.reservations.swap_remove(idx);
+        self.transactions.get_mut(transaction_id).unwrap().status = TransactionStatus::Scheduled;
+        self.in_flight.insert(message_id, (*transaction_id, ExecutionStage::Scheduled, exec::block_height()));
+        true
+    }
+
+    /// @dev Allows an owner to fund the gas reservation pool with gas of the current message.
+    /// @param sender Owner funding the pool.
+    /// @param amount Amount of gas to reserve.
+    /// @param duration Number of blocks the reservation lives.
+    pub fn reserve_gas(&mut self, sender:&ActorId, amount:u64, duration:u32)
+    {
+        self.owner_exists(sender);
+        assert!(amount >= SCHEDULED_EXECUTION_GAS, "at least {} gas is needed to schedule an execution", SCHEDULED_EXECUTION_GAS);
+        let id = exec::reserve_gas(amount, duration).expect("unable to reserve gas");
+        self.reservations.push(GasReservation{
+            id,
+            amount,
+            expires_at: exec::block_height().saturating_add(duration),
+        });
+        // GasReservation(sender, amount, duration);
+    }
+
+    /// @dev Allows to release all reservations of the pool. Transaction has to be sent by wallet.
+    /// @return Returns amount of gas released.
+    pub fn release_gas(&mut self)->u64
+    {
+        self.only_wallet();
+        self.drop_expired_reservations();
+        let mut released = 0;
+        for reservation in self.reservations.drain(..) {
+            released += exec::unreserve_gas(reservation.id).unwrap_or(0);
+        }
+        // GasRelease(released);
+        released
+    }
+
+    fn drop_expired_reservations(&mut self)
+    {
+        let now = exec::block_height();
+        self.reservations.retain(|r|r.expires_at > now);
+    }
+
+    /// @dev Returns the live reservations of the gas pool.
+    /// @return List of reservations.
+    pub fn get_gas_reservations(&self) -> Vec<GasReservationInfo>
+    {
+        let now = exec::block_height();
+        self.reservations.iter()
+        .filter(|r|r.expires_at > now)
+        .map(|r|GasReservationInfo{amount: r.amount, expires_at: r.expires_at})
+        .collect()
+    }
+
+    /// @dev Returns the confirmation status of a transaction.
+    /// @param transactionId Transaction ID.
+    /// @return Confirmation status.
+    pub fn is_confirmed(&self, transaction_id:&uint)->bool
+    {
+        let cfm_dict = self.confirmations.get(transaction_id).unwrap();
+        let required = match self.transactions.get(transaction_id).and_then(|txn|txn.snapshot) {
+            Some(snapshot) => {
+                // only the owners of the snapshot count, whatever happened to the owner set since
+                let voters = &self.owner_sets[&snapshot.version];
+                let count = voters.iter().filter(|owner|*cfm_dict.get(owner).unwrap_or(&false)).count() as uint;
+                return count >= snapshot.required;
+            },
+            None => self.required,
+        };
+        let mut count = 0;
+        for confirmed in cfm_dict.values() {
+            if *confirmed {
+                count += 1;
+            }
+            if count == required {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    // Owners voting on a transaction, the snapshot if it has one and the current owners otherwise.
+    fn voters(&self, transaction_id:&uint) -> &Vec<ActorId>
+    {
+        match self.transactions.get(transaction_id).and_then(|txn|txn.snapshot) {
+            Some(snapshot) => &self.owner_sets[&snapshot.version],
+            None => &self.owners,
+        }
+    }
+
+    fn required_for(&self, transaction_id:&uint) -> uint
+    {
+        self.transactions.get(transaction_id).and_then(|txn|txn.snapshot).map_or(self.required, |snapshot|snapshot.required)
+    }
+
+    /// @dev Appends a state-changing event to the bounded audit log, dropping the oldest entry when full.
+    /// @param actor Sender of the message which caused the event.
+    /// @param event Event to record.
+    pub fn log_event(&mut self, actor:&ActorId, event:Event)
+    {
+        if self.event_log.len() >= MAX_LOG_SIZE {
+            self.event_log.pop_front();
+        }
+        self.event_log.push_back(LogEntry{
+            seq: self.next_seq,
+            block: exec::block_height(),
+            actor: *actor,
+            event,
+        });
+        self.next_seq += 1;
+        self.notify_listeners();
+    }
+
+    // Sends the notification matching the last logged event to the interested listeners.
+    // Delivery is fire-and-forget, a failing listener never blocks the wallet.
+    fn notify_listeners(&self)
+    {
+        if self.listeners.is_empty() {
+            return;
+        }
+        let notification = match self.event_log.back().and_then(|e|Notification::from_event(&e.event)) {
+            Some(notification) => notification,
+            None => return,
+        };
+        for (listener, filter) in self.listeners.iter() {
+            if filter.accepts(&notification) {
+                let _ = msg::send(*listener, &notification, 0);
+            }
+        }
+    }
+
+    /*
+     * Internal fns
+     */
+    /// @dev Adds a new transaction to the transaction mapping, if transaction does not exist yet.
+    /// @param submitter Owner submitting the transaction.
+    /// @param txn Destination, value, data and execution rules of the transaction.
+    /// @param bond Value held until the transaction is closed.
+    /// @param walletVote Proposed as a vote in a child wallet.
+    /// @return Returns transaction ID.
+    fn add_transaction(&mut self, submitter:&ActorId, txn:NewTransaction, bond:uint, wallet_vote:bool)->uint
+    {
+        let NewTransaction{destination, value, data, gas_limit, details, depends_on, nonce, condition} = txn;
+        Self::not_null(&destination);
+        let transaction_id = self.transaction_count;
+        let snapshot = if self.snapshot_mode {
+            self.owner_sets.entry(self.owner_set_version).or_insert_with(||self.owners.clone());
+            Some(OwnerSnapshot{version: self.owner_set_version, required: self.required})
+        } else {
+            None
+        };
+        self.transactions.insert(transaction_id, Transaction{
+            submitter: *submitter,
+            details,
+            destination,
+            value,
+            data,
+            status: TransactionStatus::Pending,
+            gas_limit,
+            failure_count: 0,
+            last_failure: None,
+            snapshot,
+            bond,
+            expires_at: if self.proposal_ttl > 0 {Some(exec::block_height().saturating_add(self.proposal_ttl))} else {None},
+            depends_on: depends_on.clone(),
+            nonce,
+            condition,
+            wallet_vote,
+        });
+        if let Some(nonce) = nonce {
+            self.nonces.entry(nonce).or_default().insert(transaction_id);
+        }
+        for dependency in depends_on {
+            self.dependents.entry(dependency).or_default().insert(transaction_id);
+        }
+        self.pending_ids.insert(transaction_id);
+        self.transaction_count += 1;
+        // self.Submission(transactionId);
+        transaction_id
+    }
+
+    /*
+     * Web3 call functions
+     */
+    /// @dev Returns number of confirmations of a transaction.
+    /// @param transactionId Transaction ID.
+    /// @return Number of confirmations.
+    pub fn get_confirmation_count(&self, transaction_id:&uint)->uint
+    {
+        let cc = match self.confirmations.get(transaction_id) {
+            Some(dict) => {
+                dict.values().fold(0 as uint, |n, value|if *value {n + 1} else {n})
+            },
+            None => 0
+        };
+        cc
+    }
+
+    /// @dev Returns total number of transactions after filers are applied.
+    /// @param pending Include pending transactions.
+    /// @param executed Include executed transactions.
+    /// @return Total number of transactions after filters are applied.
+    pub fn get_transaction_count(&self, pending:bool, executed: bool)->uint
+    {
+        let mut count = 0;
+        if pending {
+            count += self.pending_ids.len() as uint;
+        }
+        if executed {
+            count += self.executed_count;
+        }
+        count
+    }
+
+    /// @dev Returns the details of a transaction.
+    /// @param transactionId Transaction ID.
+    /// @return Transaction details.
+    pub fn get_transaction(&self, transaction_id:&uint)->TransactionInfo
+    {
+        let txn = self.transactions.get(transaction_id).expect("transaction does not exist");
+        TransactionInfo {
+            submitter: txn.submitter,
+            details: txn.details.clone(),
+            destination: txn.destination,
+            value: txn.value,
+            data: txn.data.clone(),
+            status: txn.status,
+            gas_limit: txn.gas_limit,
+            failure_count: txn.failure_count,
+            last_failure: txn.last_failure.clone(),
+            snapshot: txn.snapshot,
+            bond: txn.bond,
+            expires_at: txn.expires_at,
+            depends_on: txn.depends_on.clone(),
+            nonce: txn.nonce,
+            condition: txn.condition.clone(),
+            wallet_vote: txn.wallet_vote,
+        }
+    }
+
+    /// @dev Returns a page of enabled modules.
+    /// @param from Cursor, first module of the page, `None` starts at the first module.
+    /// @param limit Maximum number of modules returned, capped by MAX_PAGE_SIZE.
+    /// @return Returns array of modules and the cursor of the next page, if any.
+    pub fn get_modules(&self, from:Option<ActorId>, limit:u32) -> (Vec<ActorId>, Option<ActorId>)
+    {
+        let limit = limit.min(MAX_PAGE_SIZE) as usize;
+        let mut modules:Vec<ActorId> = match from {
+            Some(from) => self.modules.range(from..).take(limit + 1).cloned().collect(),
+            None => self.modules.iter().take(limit + 1).cloned().collect(),
+        };
+        let next = modules.get(limit).cloned();
+        modules.truncate(limit);
+        (modules, next)
+    }
+
+    /// @dev Returns the listener programs and their filters.
+    /// @return Returns array of listeners.
+    pub fn get_listeners(&self) -> Vec<(ActorId, NotificationFilter)>
+    {
+        self.listeners.iter().map(|(listener, filter)|(*listener, filter.clone())).collect()
+    }
+
+    /// @dev Returns the invites which have not expired.
+    /// @return Returns array of invites.
+    pub fn get_invites(&self) -> Vec<OwnerInvite>
+    {
+        let now = exec::block_height();
+        self.invites.values().filter(|invite|invite.expires_at > now).cloned().collect()
+    }
+
+    /// @dev Returns the pause state and configuration.
+    /// @return Returns the block the pause ends at, the pending pause votes, the threshold and the maximum duration.
+    pub fn get_pause_state(&self) -> (Option<u32>, Vec<ActorId>, uint, u32)
+    {
+        let paused_until = if self.is_paused() {self.paused_until} else {None};
+        let votes = self.owners.iter().filter(|owner|self.pause_votes.contains(owner)).cloned().collect();
+        (paused_until, votes, self.pause_threshold, self.max_pause_duration)
+    }
+
+    /// @dev Returns the version of the owner set, bumped on every owner change, and the snapshot mode.
+    /// @return Returns the owner set version and true if new transactions are snapshotted.
+    pub fn get_owner_set_version(&self) -> (u32, bool)
+    {
+        (self.owner_set_version, self.snapshot_mode)
+    }
+
+    /// @dev Returns the executions waiting for a reply.
+    /// @return Returns array of transaction IDs, module call and allowance spend IDs for their own steps, and the step each one waits in.
+    pub fn get_executions(&self) -> Vec<(uint, ExecutionStage)>
+    {
+        self.in_flight.values().map(|(id, stage, _)|(*id, *stage)).collect()
+    }
+
+    /// @dev Returns the strict order configuration.
+    /// @return Returns the nonce of the next transaction to execute and true if strict order applies.
+    pub fn get_nonce(&self) -> (u64, bool)
+    {
+        (self.next_nonce, self.nonce_mode)
+    }
+
+    /// @dev Returns the bond configuration.
+    /// @return Returns the minimum bond and the number of blocks a transaction stays open.
+    pub fn get_bond_config(&self) -> (uint, u32)
+    {
+        (self.bond, self.proposal_ttl)
+    }
+
+    /// @dev Returns the guard program.
+    /// @return Address of the guard, if any.
+    pub fn get_guard(&self) -> Option<ActorId>
+    {
+        self.guard
+    }
+
+    /// @dev Returns the quorum mode.
+    /// @return Quorum mode.
+    pub fn get_quorum_mode(&self) -> QuorumMode
+    {
+        self.quorum_mode
+    }
+
+    /// @dev Returns list of owners.
+    /// @return List of owner addresses.
+    pub fn get_owners(&self) -> Vec<ActorId>
+    {
+        self.owners.clone()
+    }
+
+    /// @dev Returns array with owner addresses, which confirmed transaction.
+    /// @param transactionId Transaction ID.
+    /// @return Returns array of owner addresses.
+    pub fn get_confirmations(&self, transaction_id:&uint) -> Vec<ActorId> 
+    {
+        let confirmations = match self.confirmations.get(transaction_id) {
+            Some(dict) => {
+                self.voters(transaction_id).iter().filter(|owner| *dict.get(owner).unwrap_or(&false)).cloned().collect()
+            },
+            None => vec![],
+        };
+        confirmations
+    }
+
+    /// @dev Returns the allowance of an owner for the current period.
+    /// @param owner Address of owner.
+    /// @return Returns the allowance, if any.
+    pub fn get_allowance(&self, owner:&ActorId) -> Option<AllowanceInfo>
+    {
+        let now = exec::block_height();
+        self.allowances.get(owner).map(|a| {
+            let elapsed = now.saturating_sub(a.period_start);
+            let (period_start, spent) = if elapsed >= a.period {
+                (now - elapsed % a.period, 0)
+            } else {
+                (a.period_start, a.spent)
+            };
+            AllowanceInfo{
+                amount: a.amount,
+                period: a.period,
+                destination: a.destination,
+                period_start,
+                remaining: a.amount - spent,
+            }
+        })
+    }
+
+    /// @dev Returns the delegations which have not expired.
+    /// @return Returns array of delegations.
+    pub fn get_delegations(&self) -> Vec<Delegation>
+    {
+        let now = exec::block_height();
+        self.delegations.values().filter(|d|d.until_block >= now).cloned().collect()
+    }
+
+    /// @dev Returns the votes of the owners voting on a transaction, its snapshot if it has one.
+    /// @param transactionId Transaction ID.
+    /// @return Returns arrays of owners who confirmed, rejected and did neither.
+    pub fn get_votes(&self, transaction_id:&uint) -> (Vec<ActorId>, Vec<ActorId>, Vec<ActorId>)
+    {
+        let voters = self.voters(transaction_id);
+        let confirmed = self.get_confirmations(transaction_id);
+        let rejected:Vec<ActorId> = match self.rejections.get(transaction_id) {
+            Some(set) => voters.iter().filter(|owner|set.contains(owner)).cloned().collect(),
+            None => vec![],
+        };
+        let abstained = voters.iter().filter(|owner|!confirmed.contains(owner) && !rejected.contains(owner)).cloned().collect();
+        (confirmed, rejected, abstained)
+    }
+
+    /// @dev Returns the ordered history of confirmations, revocations and rejections of a transaction.
+    /// @param transactionId Transaction ID.
+    /// @return Returns array of confirmation records.
+    pub fn get_confirmation_details(&self, transaction_id:&uint) -> Vec<ConfirmationRecord>
+    {
+        self.confirmation_history.get(transaction_id).cloned().unwrap_or_default()
+    }
+
+    /// @dev Returns summaries of pruned transactions in the order they were archived.
+    /// @param fromIndex Position of the first summary in the archive.
+    /// @param limit Maximum number of summaries returned, capped by MAX_PAGE_SIZE.
+    /// @return Returns array of summaries and the current head of the hash chain.
+    pub fn get_archive(&self, from_index:u32, limit:u32) -> (Vec<ArchivedTransaction>, [u8; 32])
+    {
+        let entries = self.archive.iter().skip(from_index as usize).take(limit.min(MAX_PAGE_SIZE) as usize).cloned().collect();
+        (entries, self.archive_head)
+    }
+
+    /// @dev Returns audit log entries starting at a sequence number.
+    /// @param fromSeq First sequence number, entries already dropped from the log are skipped.
+    /// @param limit Maximum number of entries returned, capped by MAX_PAGE_SIZE.
+    /// @return Returns array of log entries.
+    pub fn get_events(&self, from_seq:u64, limit:u32) -> Vec<LogEntry>
+    {
+        let first_seq = self.event_log.front().map_or(self.next_seq, |e|e.seq);
+        let skip = from_seq.saturating_sub(first_seq) as usize;
+        self.event_log.iter().skip(skip).take(limit.min(MAX_PAGE_SIZE) as usize).cloned().collect()
+    }
+
+    /// @dev Returns a page of transaction IDs.
+    /// @param fromId Cursor, first transaction ID of the page, `None` starts at the first (or last if reversed) transaction.
+    /// @param limit Maximum number of IDs returned, capped by MAX_PAGE_SIZE.
+    /// @param reverse Walk from newest to oldest.
+    /// @param pending Include pending transactions.
+    /// @param executed Include executed transactions.
+    /// @return Returns array of transaction IDs and the cursor of the next page, if any.
+    pub fn get_transaction_ids(&self, from_id:Option<uint>, limit:u32, reverse:bool, pending:bool, executed:bool)->(Vec<uint>, Option<uint>)
+    {
+        let limit = limit.min(MAX_PAGE_SIZE) as usize;
+        let mut ids:Vec<uint> = vec![];
+        // one extra id per index is enough to know the next cursor
+        for (include, index) in [(pending, &self.pending_ids), (executed, &self.executed_ids)] {
+            if !include {
+                continue;
+            }
+            match (reverse, from_id) {
+                (false, Some(from)) => ids.extend(index.range(from..).take(limit + 1)),
+                (false, None) => ids.extend(index.iter().take(limit + 1)),
+                (true, Some(from)) => ids.extend(index.range(..=from).rev().take(limit + 1)),
+                (true, None) => ids.extend(index.iter().rev().take(limit + 1)),
+            }
+        }
+        ids.sort_unstable();
+        if reverse {
+            ids.reverse();
+        }
+        let next_cursor = ids.get(limit).cloned();
+        ids.truncate(limit);
+        (ids, next_cursor)
     }
 }
@@ -1,4 +1,5 @@
-use gtest::{Log, Program, System};
+use gtest::{Log, Program, RunResult, System, WasmProgram};
+use std::{cell::Cell, rc::Rc};
 use gstd::{prelude::*, ActorId};
 // use gear_core::ids::ProgramId;
 use multi_sig_wallet::*;
@@ -6,6 +7,7 @@ use multi_sig_wallet::wallet::*;
 
 const REQUIRED: uint = 02;
 const FROM_ID:u64 = 100001;
+const WASM_PATH: &str = "./target/wasm32-unknown-unknown/debug/multi_sig_wallet.wasm";
 
 fn actor2arr(id:&ActorId) ->[u8;32] {
     let mut ans = [0u8; 32];
@@ -30,10 +32,27 @@ fn init(sys:&System) {
     sys.init_logger();
 }
 
+// Wallet initialized with the owners 1 to 4.
+fn setup(sys:&System)->(Program<'_>, Vec<ActorId>) {
+    init(sys);
+    let program = Program::from_file(sys, WASM_PATH);
+    let owners = (1..5).map(|x|create_owner(x)).collect::<Vec<_>>();
+    send_init(&owners, &program);
+    (program, owners)
+}
+
+fn program_actor(program:&Program)->ActorId {
+    ActorId::from_slice(program.id().as_ref()).unwrap()
+}
+
 fn send_init(owners:&Vec<ActorId>, program:&Program) {
+    send_init_with(owners, REQUIRED, program);
+}
+
+fn send_init_with(owners:&Vec<ActorId>, required:uint, program:&Program) {
     let init = InitConfig {
         owners: owners.to_owned(),
-        required: REQUIRED,
+        required,
     };
     let res = program.send_bytes(FROM_ID, init.encode());
     assert!(res.log().is_empty());
@@ -65,10 +84,10 @@ fn add_owner(owners:&mut Vec<ActorId>, program:&Program) {
     assert!(1 == get_transaction_count(program, true, true));
     // confirm
     let e = confirm_transaction(program, &owners[1], &transaction_id);
-    if let Event::Confirmation{sender, transaction_id:id, executed} =  e{
+    if let Event::Confirmation{sender, transaction_id:id, dispatched} =  e{
         assert!(sender == owners[1]);
         assert!(transaction_id==id);
-        assert!(executed);
+        assert!(dispatched);
     }
 
     // after confirm
@@ -79,6 +98,11 @@ fn add_owner(owners:&mut Vec<ActorId>, program:&Program) {
     assert!(new_confirmations[0]==owners[0]);
     assert!(new_confirmations[1]==owners[1]);
 
+    // owner joins only after accepting
+    verify_owners(program, owners);
+    let res = program.send(actor2arr(&create_owner(7u8)), Action::AcceptOwnership);
+    assert!(res.main_failed());
+    accept_ownership(program, &owner5, Event::OwnerAddition{owner:owner5});
     owners.push(owner5.clone());
     verify_owners(program, owners);
 
@@ -104,10 +128,10 @@ fn remove_owner(owners:&mut Vec<ActorId>, program:&Program) {
     assert!(2 == get_transaction_count(program, true, true));
     // confirm
     let e = confirm_transaction(program, &owners[1], &transaction_id);
-    if let Event::Confirmation{sender, transaction_id:id, executed} =  e{
+    if let Event::Confirmation{sender, transaction_id:id, dispatched} =  e{
         assert!(sender == owners[1]);
         assert!(transaction_id==id);
-        assert!(executed);
+        assert!(dispatched);
     }
 
     // after confirm
@@ -142,10 +166,10 @@ fn change_requirement(owners:&Vec<ActorId>, program:&Program) {
     assert!(3 == get_transaction_count(program, true, true));
     // confirm
     let e = confirm_transaction(program, &owners[1], &transaction_id);
-    if let Event::Confirmation{sender, transaction_id:id, executed} =  e{
+    if let Event::Confirmation{sender, transaction_id:id, dispatched} =  e{
         assert!(sender == owners[1]);
         assert!(transaction_id==id);
-        assert!(executed);
+        assert!(dispatched);
     }
 
     // after confirm
@@ -179,10 +203,10 @@ fn replace_owner(owners:&mut Vec<ActorId>, program:&Program) {
     assert!(4 == get_transaction_count(program, true, true));
     // confirm
     let e = confirm_transaction(program, &owners[1], &transaction_id);
-    if let Event::Confirmation{sender, transaction_id:id, executed} =  e{
+    if let Event::Confirmation{sender, transaction_id:id, dispatched} =  e{
         assert!(sender == owners[1]);
         assert!(transaction_id==id);
-        assert!(!executed);
+        assert!(!dispatched);
     }
 
     // not enough confirmations
@@ -194,10 +218,10 @@ fn replace_owner(owners:&mut Vec<ActorId>, program:&Program) {
     assert!(new_confirmations[1]==owners[1]);
     // 3 confirmations
     let e = confirm_transaction(program, &owners[2], &transaction_id);
-    if let Event::Confirmation{sender, transaction_id:id, executed} =  e{
+    if let Event::Confirmation{sender, transaction_id:id, dispatched} =  e{
         assert!(sender == owners[2]);
         assert!(transaction_id==id);
-        assert!(executed);
+        assert!(dispatched);
     }
     assert!(3 == get_confirmation_count(program, &transaction_id));
     assert!(is_confirmed(program, &transaction_id));
@@ -208,7 +232,8 @@ fn replace_owner(owners:&mut Vec<ActorId>, program:&Program) {
     assert!(new_confirmations[2]==owners[2]);
 
 
-    // owners.push(owner5.clone());
+    verify_owners(program, owners);
+    accept_ownership(program, &owner6, Event::OwnerReplace{from:owners[3], to:owner6});
     owners[3] = owner6;
     verify_owners(program, owners);
 
@@ -217,23 +242,46 @@ fn replace_owner(owners:&mut Vec<ActorId>, program:&Program) {
     assert!(4== get_transaction_count(program, true, true));
 }
 
+fn accept_ownership(program:&Program, invitee:&ActorId, event:Event) {
+    let res = program.send(actor2arr(invitee), Action::AcceptOwnership);
+    assert!(!res.main_failed());
+    assert!(res.contains(&Log::builder().payload(event)));
+}
+
 fn submit_transaction(program:&Program,sender:&ActorId, data:&[u8])-> uint {
-    let destination = {
-        ActorId::from_slice(program.id().as_ref()).unwrap()
-    };
-    let res = program.send(actor2arr(sender), Action::SubmitTransaction{destination:destination, value:0, data:data.to_vec() });
+    submit_transaction_to(program, sender, &program_actor(program), data)
+}
+
+fn submit_transaction_to(program:&Program,sender:&ActorId, destination:&ActorId, data:&[u8])-> uint {
+    submit_with(program, sender, destination, data, vec![], None, None)
+}
+
+fn submit_with(program:&Program,sender:&ActorId, destination:&ActorId, data:&[u8], depends_on:Vec<uint>, nonce:Option<u64>, condition:Option<Condition>)-> uint {
+    let destination = destination.clone();
+    let res = program.send(actor2arr(sender), Action::SubmitTransaction{destination:destination, value:0, data:data.to_vec(), gas_limit:None, details:ProposalDetails::default(), depends_on, nonce, condition });
     assert!(!res.log().is_empty());
     assert!(!res.main_failed());
     assert!(!res.others_failed());
     
     for log in res.log() {
-        if let Ok(Event::Submission{transaction_id}) = Event::decode(&mut log.payload().as_ref()){
+        if let Ok(Event::Submission{transaction_id, ..}) = Event::decode(&mut log.payload().as_ref()){
             return transaction_id;
         }
     }
     0
 }
 
+// First event of the result `select` picks.
+fn find_event<T>(res:&RunResult, select:impl Fn(Event)->Option<T>)->T {
+    res.log().iter().find_map(|log|Event::decode(&mut log.payload().as_ref()).ok().and_then(&select)).expect("no matching event")
+}
+
+fn query<T>(program:&Program, action:Action, select:impl Fn(Event)->Option<T>)->T {
+    let res = program.send(FROM_ID, action);
+    assert!(!res.main_failed());
+    find_event(&res, select)
+}
+
 fn get_confirmation_count(program:&Program, transaction_id:&uint)->uint {
     let action = Action::GetConfirmationCount{transaction_id:*transaction_id};
     let res = program.send(FROM_ID, action);
@@ -282,7 +330,7 @@ fn get_confirmations(program:&Program, transaction_id:&uint)->Vec<ActorId> {
 }
 
 fn confirm_transaction(program:&Program, owner:&ActorId, transaction_id:&uint)->Event {
-    let action = Action::ConfirmTransaction{transaction_id:*transaction_id};
+    let action = Action::ConfirmTransaction{transaction_id:*transaction_id, comment:None};
     let res = program.send(actor2arr(&owner), action);
     assert!(!res.log().is_empty());
     assert!(!res.main_failed());
@@ -297,6 +345,27 @@ fn confirm_transaction(program:&Program, owner:&ActorId, transaction_id:&uint)->
     Event::Invalid
 }
 
+fn get_transaction(program:&Program, transaction_id:&uint)->TransactionInfo {
+    query(program, Action::GetTransaction{transaction_id:*transaction_id}, |e|match e {
+        Event::GetTransaction(info) => Some(info),
+        _ => None,
+    })
+}
+
+fn get_transaction_ids(program:&Program, from_id:Option<uint>, limit:u32, reverse:bool, pending:bool, executed:bool)->(Vec<uint>, Option<uint>) {
+    query(program, Action::GetTransactionIds{from_id, limit, reverse, pending, executed}, |e|match e {
+        Event::GetTransactionIds{ids, next_cursor} => Some((ids, next_cursor)),
+        _ => None,
+    })
+}
+
+fn get_confirmation_details(program:&Program, transaction_id:&uint)->Vec<ConfirmationRecord> {
+    query(program, Action::GetConfirmationDetails{transaction_id:*transaction_id}, |e|match e {
+        Event::GetConfirmationDetails(details) => Some(details),
+        _ => None,
+    })
+}
+
 fn get_transaction_count(program:&Program, pending:bool, executed:bool) ->uint {
     let action = Action::GetTransactionCount{pending, executed};
     let res = program.send(FROM_ID, action);
@@ -316,17 +385,1073 @@ fn get_transaction_count(program:&Program, pending:bool, executed:bool) ->uint {
 #[test]
 fn basics() {
     let sys = System::new();
-    init(&sys);
-    let program = Program::from_file(
-        &sys,
-        "./target/wasm32-unknown-unknown/debug/multi_sig_wallet.wasm",
-    );
-    let mut owners = (1..5).map(|x|create_owner(x)).collect::<Vec<_>>();
-    // init
-    send_init(&owners, &program);
+    let (program, mut owners) = setup(&sys);
     // add owner
     add_owner(&mut owners, &program);
     remove_owner(&mut owners, &program);
     change_requirement(&owners, &program);
     replace_owner(&mut owners, &program);
 }
+
+#[test]
+fn transaction_ids() {
+    let sys = System::new();
+    let (program, owners) = setup(&sys);
+
+    // 0, 2, 4 stay pending, 1 and 3 are executed
+    let action = Action::ChangeRequirement{required:REQUIRED};
+    for id in 0..5 {
+        assert!(id == submit_transaction(&program, &owners[0], &action.encode()));
+        if id % 2 == 1 {
+            confirm_transaction(&program, &owners[1], &id);
+        }
+    }
+    assert!(get_transaction_ids(&program, None, 10, false, true, false) == (vec![0, 2, 4], None));
+    assert!(get_transaction_ids(&program, None, 10, false, false, true) == (vec![1, 3], None));
+    assert!(get_transaction_ids(&program, None, 2, false, true, true) == (vec![0, 1], Some(2)));
+    assert!(get_transaction_ids(&program, Some(2), 2, false, true, true) == (vec![2, 3], Some(4)));
+    assert!(get_transaction_ids(&program, Some(4), 2, false, true, true) == (vec![4], None));
+    assert!(get_transaction_ids(&program, None, 2, true, true, false) == (vec![4, 2], Some(0)));
+    assert!(get_transaction_ids(&program, Some(3), 10, true, false, true) == (vec![3, 1], None));
+}
+
+#[test]
+fn confirmation_details() {
+    let sys = System::new();
+    let (program, owners) = setup(&sys);
+
+    let action = Action::ChangeRequirement{required:REQUIRED};
+    let transaction_id = submit_transaction(&program, &owners[0], &action.encode());
+    let res = program.send(actor2arr(&owners[0]), Action::RevokeConfirmation{transaction_id, comment:Some("wrong amount".into())});
+    assert!(!res.main_failed());
+    let res = program.send(actor2arr(&owners[1]), Action::ConfirmTransaction{transaction_id, comment:Some("looks fine".into())});
+    assert!(!res.main_failed());
+    assert!(get_confirmations(&program, &transaction_id) == vec![owners[1]]);
+
+    let details = get_confirmation_details(&program, &transaction_id);
+    assert!(3 == details.len());
+    assert!(details[0].owner == owners[0] && details[0].action == ConfirmationAction::Confirm && details[0].comment.is_none());
+    assert!(details[1].owner == owners[0] && details[1].action == ConfirmationAction::Revoke);
+    assert!(details[1].comment.as_deref() == Some("wrong amount"));
+    assert!(details[2].owner == owners[1] && details[2].action == ConfirmationAction::Confirm);
+    assert!(details[2].comment.as_deref() == Some("looks fine"));
+    assert!(details.windows(2).all(|w|w[0].block <= w[1].block && w[0].timestamp <= w[1].timestamp));
+}
+
+#[test]
+fn audit_log() {
+    let sys = System::new();
+    let (program, owners) = setup(&sys);
+
+    let action = Action::ChangeRequirement{required:REQUIRED + 1};
+    let transaction_id = submit_transaction(&program, &owners[0], &action.encode());
+    confirm_transaction(&program, &owners[1], &transaction_id);
+    // queries are not logged
+    get_transaction_count(&program, true, true);
+
+    let res = program.send(FROM_ID, Action::GetEvents{from_seq:0, limit:10});
+    let mut entries = vec![];
+    for log in res.log() {
+        if let Ok(Event::GetEvents(e)) = Event::decode(&mut log.payload().as_ref()){
+            entries = e;
+        }
+    }
+    assert!(4 == entries.len());
+    assert!(entries.iter().enumerate().all(|(i, e)|e.seq == i as u64));
+    assert!(entries[0].actor == owners[0] && matches!(entries[0].event, Event::Submission{..}));
+    // the confirmation dispatches the call, the execution is settled by its reply
+    assert!(entries[1].actor == owners[1] && matches!(entries[1].event, Event::Confirmation{dispatched:true, ..}));
+    assert!(entries[2].actor == program_actor(&program));
+    assert!(matches!(entries[2].event, Event::RequirementChange{from, to} if from == REQUIRED && to == REQUIRED + 1));
+    assert!(entries[3].actor == program_actor(&program) && matches!(entries[3].event, Event::Execution{..}));
+
+    let res = program.send(FROM_ID, Action::GetEvents{from_seq:3, limit:10});
+    assert!(res.log().iter().any(|log|matches!(Event::decode(&mut log.payload().as_ref()), Ok(Event::GetEvents(e)) if e.len() == 1 && e[0].seq == 3)));
+}
+
+#[test]
+fn prune_transactions() {
+    let sys = System::new();
+    let (program, owners) = setup(&sys);
+
+    // 0 and 1 are executed, 2 stays pending
+    let action = Action::ChangeRequirement{required:REQUIRED};
+    for id in 0..3 {
+        assert!(id == submit_transaction(&program, &owners[0], &action.encode()));
+        if id < 2 {
+            confirm_transaction(&program, &owners[1], &id);
+        }
+    }
+    let prune = Action::PruneTransactions{before_id:3};
+    let transaction_id = submit_transaction(&program, &owners[0], &prune.encode());
+    confirm_transaction(&program, &owners[1], &transaction_id);
+
+    let (entries, head) = query(&program, Action::GetArchive{from_index:0, limit:10}, |e|match e {
+        Event::GetArchive{entries, head} => Some((entries, head)),
+        _ => None,
+    });
+    assert!(entries.iter().map(|e|e.id).collect::<Vec<_>>() == vec![0, 1]);
+    assert!(entries[0].chain_hash != entries[1].chain_hash && entries[1].chain_hash == head);
+    assert!(entries.iter().all(|e|e.status == TransactionStatus::Executed));
+
+    // counters still cover pruned transactions
+    assert!(3 == get_transaction_count(&program, false, true));
+    assert!(1 == get_transaction_count(&program, true, false));
+    assert!(get_transaction_ids(&program, None, 10, false, true, true) == (vec![2, 3], None));
+    assert!(0 == get_confirmation_count(&program, &0));
+}
+
+#[test]
+fn reject_transaction() {
+    let sys = System::new();
+    let (program, owners) = setup(&sys);
+
+    let action = Action::ChangeRequirement{required:REQUIRED + 1};
+    let transaction_id = submit_transaction(&program, &owners[0], &action.encode());
+    let reject = |owner:&ActorId| {
+        let res = program.send(actor2arr(owner), Action::RejectTransaction{transaction_id, comment:None});
+        assert!(!res.main_failed());
+        find_event(&res, |e|match e {
+            Event::Rejection{cancelled, ..} => Some(cancelled),
+            _ => None,
+        })
+    };
+    // the submitter changes its mind, its confirmation is replaced
+    assert!(!reject(&owners[0]));
+    assert!(!reject(&owners[1]));
+    let res = program.send(FROM_ID, Action::GetVotes{transaction_id});
+    assert!(res.contains(&Log::builder().payload(Event::GetVotes{approve:vec![], reject:vec![owners[0], owners[1]], abstain:vec![owners[2], owners[3]]})));
+
+    // two owners left can still reach the requirement, one can't
+    assert!(reject(&owners[2]));
+    assert!(get_transaction(&program, &transaction_id).status == TransactionStatus::Cancelled);
+    let res = program.send(actor2arr(&owners[3]), Action::ConfirmTransaction{transaction_id, comment:None});
+    assert!(res.main_failed());
+    assert!(0 == get_transaction_count(&program, true, true));
+}
+
+#[test]
+fn delegation() {
+    let sys = System::new();
+    let (program, owners) = setup(&sys);
+    let delegate = create_owner(9);
+
+    let res = program.send(actor2arr(&owners[1]), Action::DelegateTo{delegate, until_block:1000});
+    assert!(!res.main_failed());
+    let delegations = vec![Delegation{owner:owners[1], delegate, until_block:1000}];
+    assert!(program.send(FROM_ID, Action::GetDelegations).contains(&Log::builder().payload(Event::GetDelegations(delegations))));
+
+    // the delegate's confirmation counts as the owner's one
+    let action = Action::ChangeRequirement{required:REQUIRED + 1};
+    let transaction_id = submit_transaction(&program, &owners[0], &action.encode());
+    let e = confirm_transaction(&program, &delegate, &transaction_id);
+    assert!(matches!(e, Event::Confirmation{dispatched:true, ..}));
+    assert!(get_confirmations(&program, &transaction_id) == vec![owners[0], owners[1]]);
+    // the history tells who actually acted
+    let details = get_confirmation_details(&program, &transaction_id);
+    assert!(details[0].owner == owners[0] && details[0].delegate.is_none());
+    assert!(details[1].owner == owners[1] && details[1].delegate == Some(delegate));
+
+    let res = program.send(actor2arr(&owners[1]), Action::Undelegate);
+    assert!(!res.main_failed());
+    let transaction_id = submit_transaction(&program, &owners[0], &action.encode());
+    let res = program.send(actor2arr(&delegate), Action::ConfirmTransaction{transaction_id, comment:None});
+    assert!(res.main_failed());
+}
+
+#[test]
+fn nested_wallets() {
+    let sys = System::new();
+    init(&sys);
+    let board = Program::from_file(&sys, WASM_PATH);
+    let department = Program::from_file(&sys, WASM_PATH);
+    let board_id = program_actor(&board);
+    let department_id = program_actor(&department);
+    let (owner1, owner2, owner3) = (create_owner(1), create_owner(2), create_owner(3));
+    send_init(&vec![owner1, owner2], &board);
+    send_init(&vec![board_id, owner3], &department);
+
+    // the department proposal needs the board confirmation
+    let action = Action::ChangeRequirement{required:1};
+    let child_id = submit_transaction(&department, &owner3, &action.encode());
+    assert!(!is_confirmed(&department, &child_id));
+
+    let res = board.send(actor2arr(&owner1), Action::ConfirmOnWallet{wallet:department_id, transaction_id:child_id});
+    assert!(!res.main_failed());
+    let transaction_id = find_event(&res, |e|match e {
+        Event::Submission{transaction_id, ..} => Some(transaction_id),
+        _ => None,
+    });
+    let e = confirm_transaction(&board, &owner2, &transaction_id);
+    assert!(matches!(e, Event::Confirmation{dispatched:true, ..}));
+    assert!(get_confirmations(&department, &child_id) == vec![board_id, owner3]);
+    assert!(get_transaction(&department, &child_id).status == TransactionStatus::Executed);
+
+    // the department can't become an owner of the board
+    let add = Action::AddOwner(department_id);
+    let transaction_id = submit_transaction(&board, &owner1, &add.encode());
+    let res = board.send(actor2arr(&owner2), Action::ConfirmTransaction{transaction_id, comment:None});
+    assert!(!res.main_failed());
+    assert!(get_transaction(&board, &transaction_id).status == TransactionStatus::Pending);
+    verify_owners(&board, &vec![owner1, owner2]);
+
+    // nor can the board vote in one of its owners
+    let res = department.send(actor2arr(&owner3), Action::ConfirmOnWallet{wallet:board_id, transaction_id:0});
+    assert!(res.main_failed());
+}
+
+fn get_quorum(program:&Program)->(QuorumMode, uint) {
+    query(program, Action::GetQuorum, |e|match e {
+        Event::GetQuorum{mode, required} => Some((mode, required)),
+        _ => None,
+    })
+}
+
+#[test]
+fn quorum_fraction() {
+    let sys = System::new();
+    let (program, mut owners) = setup(&sys);
+
+    let mode = QuorumMode::Fraction{numerator:2, denominator:3, floor:2};
+    let transaction_id = submit_transaction(&program, &owners[0], &Action::ChangeQuorumMode{mode}.encode());
+    confirm_transaction(&program, &owners[1], &transaction_id);
+    assert!(get_quorum(&program) == (mode, 3));
+
+    // 2/3 of 5 owners rounded up
+    let owner5 = create_owner(5);
+    let transaction_id = submit_transaction(&program, &owners[0], &Action::AddOwner(owner5).encode());
+    confirm_transaction(&program, &owners[1], &transaction_id);
+    confirm_transaction(&program, &owners[2], &transaction_id);
+    assert!(get_quorum(&program) == (mode, 3));
+    accept_ownership(&program, &owner5, Event::OwnerAddition{owner:owner5});
+    owners.push(owner5);
+    verify_owners(&program, &owners);
+    assert!(get_quorum(&program) == (mode, 4));
+
+    // fixed requirements are refused while the fraction applies
+    let transaction_id = submit_transaction(&program, &owners[0], &Action::ChangeRequirement{required:2}.encode());
+    confirm_transaction(&program, &owners[1], &transaction_id);
+    confirm_transaction(&program, &owners[2], &transaction_id);
+    let res = program.send(actor2arr(&owners[3]), Action::ConfirmTransaction{transaction_id, comment:None});
+    assert!(!res.main_failed());
+    assert!(get_transaction(&program, &transaction_id).failure_count == 1);
+    assert!(get_quorum(&program) == (mode, 4));
+}
+
+#[test]
+fn owner_snapshot() {
+    let sys = System::new();
+    let (program, owners) = setup(&sys);
+
+    let transaction_id = submit_transaction(&program, &owners[0], &Action::ChangeSnapshotMode{enabled:true}.encode());
+    confirm_transaction(&program, &owners[1], &transaction_id);
+    assert!(get_transaction(&program, &transaction_id).snapshot.is_none());
+
+    let snapshotted = submit_transaction(&program, &owners[0], &Action::ChangeMaxRetries{max_retries:5}.encode());
+    assert!(get_transaction(&program, &snapshotted).snapshot == Some(OwnerSnapshot{version:0, required:2}));
+
+    // raise the requirement and remove an owner, the snapshot keeps 2 out of the first 4 owners
+    let transaction_id = submit_transaction(&program, &owners[0], &Action::ChangeRequirement{required:3}.encode());
+    confirm_transaction(&program, &owners[1], &transaction_id);
+    let transaction_id = submit_transaction(&program, &owners[0], &Action::RemoveOwner(owners[3]).encode());
+    confirm_transaction(&program, &owners[1], &transaction_id);
+    confirm_transaction(&program, &owners[2], &transaction_id);
+    let res = program.send(FROM_ID, Action::GetOwnerSetVersion);
+    assert!(res.contains(&Log::builder().payload(Event::GetOwnerSetVersion{version:1, snapshot_mode:true})));
+    assert!(!is_confirmed(&program, &snapshotted));
+
+    confirm_transaction(&program, &owners[3], &snapshotted);
+    assert!(is_confirmed(&program, &snapshotted));
+    assert!(get_transaction(&program, &snapshotted).status == TransactionStatus::Executed);
+}
+
+#[test]
+fn proposal_bond() {
+    let sys = System::new();
+    let (program, owners) = setup(&sys);
+
+    let transaction_id = submit_transaction(&program, &owners[0], &Action::ChangeBondConfig{bond:0, ttl:10}.encode());
+    confirm_transaction(&program, &owners[1], &transaction_id);
+    let res = program.send(FROM_ID, Action::GetBondConfig);
+    assert!(res.contains(&Log::builder().payload(Event::GetBondConfig{bond:0, ttl:10})));
+
+    // expired transactions can't be confirmed anymore, anyone can cancel them
+    let expiring = submit_transaction(&program, &owners[0], &Action::ChangeMaxRetries{max_retries:5}.encode());
+    assert!(get_transaction(&program, &expiring).expires_at.is_some());
+    let res = program.send(FROM_ID, Action::ExpireTransaction{transaction_id:expiring});
+    assert!(res.main_failed());
+    sys.spend_blocks(10);
+    let res = program.send(actor2arr(&owners[1]), Action::ConfirmTransaction{transaction_id:expiring, comment:None});
+    assert!(res.main_failed());
+    let res = program.send(FROM_ID, Action::ExpireTransaction{transaction_id:expiring});
+    assert!(res.contains(&Log::builder().payload(Event::Cancellation{transaction_id:expiring})));
+    assert!(get_transaction(&program, &expiring).status == TransactionStatus::Cancelled);
+
+    // only the submitter can withdraw a transaction
+    let withdrawn = submit_transaction(&program, &owners[0], &Action::ChangeMaxRetries{max_retries:5}.encode());
+    let res = program.send(actor2arr(&owners[1]), Action::CancelTransaction{transaction_id:withdrawn});
+    assert!(res.main_failed());
+    let res = program.send(actor2arr(&owners[0]), Action::CancelTransaction{transaction_id:withdrawn});
+    assert!(res.contains(&Log::builder().payload(Event::Cancellation{transaction_id:withdrawn})));
+    assert!(get_transaction(&program, &withdrawn).status == TransactionStatus::Cancelled);
+
+    let transaction_id = submit_transaction(&program, &owners[0], &Action::ChangeBondConfig{bond:1000, ttl:0}.encode());
+    confirm_transaction(&program, &owners[1], &transaction_id);
+    let action = Action::SubmitTransaction{destination:program_actor(&program), value:0, data:vec![], gas_limit:None, details:ProposalDetails::default(), depends_on:vec![], nonce:None, condition:None};
+    let res = program.send(actor2arr(&owners[0]), action);
+    assert!(res.main_failed());
+}
+
+#[test]
+fn dependencies() {
+    let sys = System::new();
+    let (program, owners) = setup(&sys);
+    let wallet = program_actor(&program);
+
+    let first = submit_transaction(&program, &owners[0], &Action::ChangeMaxRetries{max_retries:5}.encode());
+    let second = submit_with(&program, &owners[0], &wallet, &Action::ChangeMaxRetries{max_retries:6}.encode(), vec![first], None, None);
+    assert!(get_transaction(&program, &second).depends_on == vec![first]);
+
+    // confirmed, but waiting for the first one
+    confirm_transaction(&program, &owners[1], &second);
+    assert!(is_confirmed(&program, &second));
+    assert!(get_transaction(&program, &second).status == TransactionStatus::Pending);
+    let res = program.send(actor2arr(&owners[1]), Action::ExecuteTransaction{transaction_id:second});
+    assert!(res.main_failed());
+
+    // executing the first one cascades to the second
+    confirm_transaction(&program, &owners[1], &first);
+    assert!(get_transaction(&program, &first).status == TransactionStatus::Executed);
+    assert!(get_transaction(&program, &second).status == TransactionStatus::Executed);
+
+    // only earlier, not cancelled transactions can be depended on
+    let data = Action::ChangeMaxRetries{max_retries:7}.encode();
+    let next_id = second + 1;
+    let action = Action::SubmitTransaction{destination:wallet, value:0, data:data.clone(), gas_limit:None, details:ProposalDetails::default(), depends_on:vec![next_id], nonce:None, condition:None};
+    assert!(program.send(actor2arr(&owners[0]), action).main_failed());
+    let cancelled = submit_transaction(&program, &owners[0], &data);
+    program.send(actor2arr(&owners[0]), Action::CancelTransaction{transaction_id:cancelled});
+    let action = Action::SubmitTransaction{destination:wallet, value:0, data, gas_limit:None, details:ProposalDetails::default(), depends_on:vec![cancelled], nonce:None, condition:None};
+    assert!(program.send(actor2arr(&owners[0]), action).main_failed());
+
+    // dependents are cancelled along with the transaction they wait for
+    let root = submit_transaction(&program, &owners[0], &Action::ChangeMaxRetries{max_retries:8}.encode());
+    let child = submit_with(&program, &owners[1], &wallet, &Action::ChangeMaxRetries{max_retries:9}.encode(), vec![root], None, None);
+    let grandchild = submit_with(&program, &owners[2], &wallet, &Action::ChangeMaxRetries{max_retries:10}.encode(), vec![child], None, None);
+    assert!(!program.send(actor2arr(&owners[0]), Action::CancelTransaction{transaction_id:root}).main_failed());
+    for transaction_id in [root, child, grandchild] {
+        assert!(get_transaction(&program, &transaction_id).status == TransactionStatus::Cancelled);
+    }
+}
+
+#[test]
+fn strict_order() {
+    let sys = System::new();
+    let (program, owners) = setup(&sys);
+    let wallet = program_actor(&program);
+
+    let transaction_id = submit_transaction(&program, &owners[0], &Action::ChangeNonceMode{enabled:true}.encode());
+    confirm_transaction(&program, &owners[1], &transaction_id);
+
+    let first = submit_transaction(&program, &owners[0], &Action::ChangeMaxRetries{max_retries:5}.encode());
+    let second = submit_transaction(&program, &owners[0], &Action::ChangeMaxRetries{max_retries:6}.encode());
+    assert!(get_transaction(&program, &first).nonce == Some(0));
+    assert!(get_transaction(&program, &second).nonce == Some(1));
+
+    // confirmed, but waiting for nonce 0
+    confirm_transaction(&program, &owners[1], &second);
+    assert!(get_transaction(&program, &second).status == TransactionStatus::Pending);
+    let res = program.send(actor2arr(&owners[1]), Action::ExecuteTransaction{transaction_id:second});
+    assert!(res.main_failed());
+
+    // nonces can't leave a gap
+    let data = Action::ChangeMaxRetries{max_retries:7}.encode();
+    let action = Action::SubmitTransaction{destination:wallet, value:0, data:data.clone(), gas_limit:None, details:ProposalDetails::default(), depends_on:vec![], nonce:Some(3), condition:None};
+    assert!(program.send(actor2arr(&owners[0]), action).main_failed());
+
+    // the replacement invalidates the first transaction and unblocks the second
+    let replacement = submit_with(&program, &owners[0], &wallet, &data, vec![], Some(0), None);
+    confirm_transaction(&program, &owners[1], &replacement);
+    assert!(get_transaction(&program, &replacement).status == TransactionStatus::Executed);
+    assert!(get_transaction(&program, &first).status == TransactionStatus::Cancelled);
+    assert!(get_transaction(&program, &second).status == TransactionStatus::Executed);
+    let res = program.send(FROM_ID, Action::GetNonce);
+    assert!(res.contains(&Log::builder().payload(Event::GetNonce{next_nonce:2, strict:true})));
+
+    // strict order is only left once no nonce is pending
+    let pending = submit_transaction(&program, &owners[0], &Action::ChangeMaxRetries{max_retries:8}.encode());
+    let off = submit_with(&program, &owners[0], &wallet, &Action::ChangeNonceMode{enabled:false}.encode(), vec![], Some(2), None);
+    let res = program.send(actor2arr(&owners[1]), Action::ConfirmTransaction{transaction_id:off, comment:None});
+    assert!(!res.main_failed() && res.others_failed());
+    assert!(!program.send(actor2arr(&owners[0]), Action::CancelTransaction{transaction_id:pending}).main_failed());
+    assert!(!program.send(actor2arr(&owners[1]), Action::RetryTransaction{transaction_id:off}).main_failed());
+    let res = program.send(FROM_ID, Action::GetNonce);
+    assert!(res.contains(&Log::builder().payload(Event::GetNonce{next_nonce:3, strict:false})));
+}
+
+#[test]
+fn conditional_execution() {
+    let sys = System::new();
+    let (program, owners) = setup(&sys);
+    let wallet = program_actor(&program);
+
+    let data = Action::ChangeMaxRetries{max_retries:5}.encode();
+    let action = Action::SubmitTransaction{destination:wallet, value:0, data:data.clone(), gas_limit:None, details:ProposalDetails::default(), depends_on:vec![], nonce:None, condition:Some(Condition::BlockRange{from:2000, to:1000})};
+    assert!(program.send(actor2arr(&owners[0]), action).main_failed());
+
+    let condition = Condition::BlockRange{from:1000, to:2000};
+    let transaction_id = submit_with(&program, &owners[0], &wallet, &data, vec![], None, Some(condition.clone()));
+    assert!(get_transaction(&program, &transaction_id).condition == Some(condition));
+
+    // too early, the transaction stays pending without counting as a failure
+    let res = program.send(actor2arr(&owners[1]), Action::ConfirmTransaction{transaction_id, comment:None});
+    assert!(!res.main_failed());
+    let transaction = get_transaction(&program, &transaction_id);
+    assert!(transaction.status == TransactionStatus::Pending);
+    assert!(transaction.failure_count == 0);
+    let res = program.send(FROM_ID, Action::GetEvents{from_seq:0, limit:100});
+    assert!(res.log().iter().any(|log|match Event::decode(&mut log.payload().as_ref()) {
+        Ok(Event::GetEvents(entries)) => entries.iter().any(|entry|matches!(entry.event, Event::ConditionNotMet{..})),
+        _ => false,
+    }));
+
+    sys.spend_blocks(1000);
+    let res = program.send(actor2arr(&owners[1]), Action::ExecuteTransaction{transaction_id});
+    assert!(!res.main_failed());
+    assert!(get_transaction(&program, &transaction_id).status == TransactionStatus::Executed);
+}
+
+#[test]
+fn listeners() {
+    let sys = System::new();
+    let (program, owners) = setup(&sys);
+    let listener = create_owner(7);
+
+    let filter = NotificationFilter{submission:true, execution:true, ..Default::default()};
+    let action = Action::AddListener{listener, filter};
+    let transaction_id = submit_transaction(&program, &owners[0], &action.encode());
+    confirm_transaction(&program, &owners[1], &transaction_id);
+
+    let action = Action::ChangeRequirement{required:REQUIRED};
+    let res = program.send(actor2arr(&owners[0]), Action::SubmitTransaction{destination:program_actor(&program), value:0, data:action.encode(), gas_limit:None, details:ProposalDetails::default(), depends_on:vec![], nonce:None, condition:None});
+    assert!(res.contains(&Log::builder().dest(actor2arr(&listener)).payload(Notification::Submission{transaction_id:1})));
+
+    let res = program.send(actor2arr(&owners[1]), Action::ConfirmTransaction{transaction_id:1, comment:None});
+    assert!(res.contains(&Log::builder().dest(actor2arr(&listener)).payload(Notification::Execution{transaction_id:1})));
+    // not subscribed to confirmations
+    let confirmation = Notification::Confirmation{owner:owners[1], transaction_id:1};
+    assert!(!res.contains(&Log::builder().dest(actor2arr(&listener)).payload(confirmation)));
+}
+
+fn get_pause_state(program:&Program)->Option<u32> {
+    query(program, Action::GetPauseState, |e|match e {
+        Event::GetPauseState{paused_until, ..} => Some(paused_until),
+        _ => None,
+    })
+}
+
+#[test]
+fn emergency_pause() {
+    let sys = System::new();
+    let (program, owners) = setup(&sys);
+    let outsider = create_owner(8);
+
+    let action = Action::ChangePauseConfig{threshold:2, max_duration:10};
+    let transaction_id = submit_transaction(&program, &owners[0], &action.encode());
+    confirm_transaction(&program, &owners[1], &transaction_id);
+
+    assert!(!program.send(actor2arr(&owners[3]), Action::Pause).main_failed());
+    assert!(get_pause_state(&program).is_none());
+    assert!(!program.send(actor2arr(&owners[2]), Action::Pause).main_failed());
+    assert!(get_pause_state(&program).is_some());
+
+    // outgoing calls are blocked, governance goes on
+    let transfer = Action::SubmitTransaction{destination:outsider, value:0, data:vec![], gas_limit:None, details:ProposalDetails::default(), depends_on:vec![], nonce:None, condition:None};
+    assert!(program.send(actor2arr(&owners[0]), transfer).main_failed());
+    let transaction_id = submit_transaction(&program, &owners[0], &Action::Unpause.encode());
+    confirm_transaction(&program, &owners[1], &transaction_id);
+    assert!(get_pause_state(&program).is_none());
+
+    // a pause ends by itself
+    assert!(!program.send(actor2arr(&owners[3]), Action::Pause).main_failed());
+    assert!(!program.send(actor2arr(&owners[2]), Action::Pause).main_failed());
+    assert!(get_pause_state(&program).is_some());
+    sys.spend_blocks(11);
+    assert!(get_pause_state(&program).is_none());
+}
+
+#[test]
+fn executions_in_flight() {
+    let sys = System::new();
+    let (program, owners) = setup(&sys);
+    // an account never replies, its calls stay in flight
+    let outsider = create_owner(8);
+
+    let first = submit_transaction_to(&program, &owners[0], &outsider, b"first");
+    let second = submit_transaction_to(&program, &owners[0], &outsider, b"second");
+    confirm_transaction(&program, &owners[1], &first);
+    confirm_transaction(&program, &owners[1], &second);
+    let res = program.send(FROM_ID, Action::GetExecutions);
+    let executions = vec![(first, ExecutionStage::Call), (second, ExecutionStage::Call)];
+    assert!(res.log().iter().any(|log|matches!(Event::decode(&mut log.payload().as_ref()), Ok(Event::GetExecutions(e)) if e.len() == 2 && executions.iter().all(|x|e.contains(x)))));
+    assert!(get_transaction(&program, &first).status == TransactionStatus::Executing);
+    let res = program.send(actor2arr(&owners[2]), Action::ConfirmTransaction{transaction_id:first, comment:None});
+    assert!(res.main_failed());
+
+    // the wallet keeps working meanwhile
+    let transaction_id = submit_transaction(&program, &owners[0], &Action::ChangeMaxRetries{max_retries:5}.encode());
+    confirm_transaction(&program, &owners[1], &transaction_id);
+    assert!(get_transaction(&program, &transaction_id).status == TransactionStatus::Executed);
+
+    // the wallet gives up on a call, it counts as failed
+    assert!(program.send(actor2arr(&owners[0]), Action::AbortExecution{transaction_id:first}).main_failed());
+    let transaction_id = submit_transaction(&program, &owners[0], &Action::AbortExecution{transaction_id:first}.encode());
+    confirm_transaction(&program, &owners[1], &transaction_id);
+    let transaction = get_transaction(&program, &first);
+    assert!(transaction.status == TransactionStatus::Pending && transaction.failure_count == 1);
+    assert!(transaction.last_failure == Some("aborted".into()));
+    assert!(!program.send(actor2arr(&owners[0]), Action::CancelTransaction{transaction_id:first}).main_failed());
+
+    // an overdue reply can be given up on by any owner
+    sys.spend_blocks(EXECUTION_TIMEOUT);
+    let res = program.send(actor2arr(&owners[2]), Action::AbortExecution{transaction_id:second});
+    assert!(res.contains(&Log::builder().payload(Event::ExecutionAborted{transaction_id:second})));
+    assert!(get_transaction(&program, &second).status == TransactionStatus::Pending);
+    let res = program.send(FROM_ID, Action::GetExecutions);
+    assert!(res.contains(&Log::builder().payload(Event::GetExecutions(vec![]))));
+    // a reply arriving now would be ignored, the transaction is retried instead
+    assert!(!program.send(actor2arr(&owners[2]), Action::RetryTransaction{transaction_id:second}).main_failed());
+    assert!(get_transaction(&program, &second).status == TransactionStatus::Executing);
+}
+
+// Payload making `hostile` submit (and, with required 1, immediately execute) a call of `action` on `wallet`.
+fn hostile_payload(wallet:&ActorId, action:Action)->Vec<u8> {
+    Action::SubmitTransaction{destination:wallet.clone(), value:0, data:action.encode(), gas_limit:None, details:ProposalDetails::default(), depends_on:vec![], nonce:None, condition:None}.encode()
+}
+
+#[test]
+fn reentrancy() {
+    let sys = System::new();
+    init(&sys);
+    let wallet = Program::from_file(&sys, WASM_PATH);
+    // a second wallet plays the hostile destination, it messages the first wallet back while being called
+    let hostile = Program::from_file(&sys, WASM_PATH);
+    let wallet_id = program_actor(&wallet);
+    let hostile_id = program_actor(&hostile);
+    let (owner1, owner2, owner3) = (create_owner(1), create_owner(2), create_owner(3));
+    send_init(&vec![owner1, owner2, hostile_id], &wallet);
+    send_init_with(&vec![wallet_id, owner3], 1, &hostile);
+
+    // re-entrant confirmation
+    let data = hostile_payload(&wallet_id, Action::ConfirmTransaction{transaction_id:0, comment:None});
+    let transaction_id = submit_transaction_to(&wallet, &owner1, &hostile_id, &data);
+    assert!(transaction_id == 0);
+    let res = wallet.send(actor2arr(&owner2), Action::ConfirmTransaction{transaction_id, comment:None});
+    assert!(!res.main_failed());
+    assert!(res.others_failed()); // the hostile confirmation was rejected
+    assert!(get_transaction(&wallet, &transaction_id).status == TransactionStatus::Executed);
+    assert!(get_confirmations(&wallet, &transaction_id) == vec![owner1, owner2]);
+
+    // re-entrant revocation and execution, the hostile program confirms first and so triggers the execution
+    for transaction_id in [1, 2] {
+        let reentry = if transaction_id == 1 {
+            Action::RevokeConfirmation{transaction_id, comment:None}
+        } else {
+            Action::ExecuteTransaction{transaction_id}
+        };
+        let data = hostile_payload(&wallet_id, reentry);
+        assert!(transaction_id == submit_transaction_to(&wallet, &owner1, &hostile_id, &data));
+        let confirm = Action::ConfirmTransaction{transaction_id, comment:None}.encode();
+        let res = hostile.send(actor2arr(&owner3), Action::SubmitTransaction{destination:wallet_id, value:0, data:confirm, gas_limit:None, details:ProposalDetails::default(), depends_on:vec![], nonce:None, condition:None});
+        assert!(!res.main_failed());
+        assert!(res.others_failed());
+        assert!(get_transaction(&wallet, &transaction_id).status == TransactionStatus::Executed);
+        assert!(2 == get_confirmation_count(&wallet, &transaction_id));
+    }
+}
+
+fn get_modules(program:&Program, from:Option<ActorId>, limit:u32)->(Vec<ActorId>, Option<ActorId>) {
+    query(program, Action::GetModules{from, limit}, |e|match e {
+        Event::GetModules{modules, next} => Some((modules, next)),
+        _ => None,
+    })
+}
+
+#[test]
+fn modules() {
+    let sys = System::new();
+    let (program, owners) = setup(&sys);
+    // a second wallet answers the calls of the modules
+    let other = Program::from_file(&sys, WASM_PATH);
+    send_init(&owners, &other);
+    let other_id = program_actor(&other);
+    let modules = (7..10).map(|x|create_owner(x)).collect::<Vec<_>>();
+
+    for module in &modules {
+        let transaction_id = submit_transaction(&program, &owners[0], &Action::EnableModule{module:*module}.encode());
+        confirm_transaction(&program, &owners[1], &transaction_id);
+    }
+    assert!(get_modules(&program, None, 2) == (modules[..2].to_vec(), Some(modules[2])));
+    assert!(get_modules(&program, Some(modules[2]), 2) == (modules[2..].to_vec(), None));
+
+    // a module calls without confirmations and is told the outcome
+    let module = modules[0];
+    let call = Action::ExecFromModule{destination:other_id, value:0, data:Action::GetOwners.encode()}.encode();
+    let res = program.send_bytes(actor2arr(&module), call.clone());
+    assert!(!res.main_failed());
+    assert!(res.contains(&Log::builder().payload(Event::ModuleCallDispatched{call_id:0})));
+    let outcome = Event::ExecutionFromModule{module, destination:other_id, value:0, failure:None};
+    assert!(res.contains(&Log::builder().dest(actor2arr(&module)).payload(outcome)));
+
+    // nor other accounts nor the wallet itself as destination
+    assert!(program.send_bytes(actor2arr(&owners[0]), call.clone()).main_failed());
+    let governance = Action::ExecFromModule{destination:program_actor(&program), value:0, data:Action::ChangeRequirement{required:1}.encode()};
+    assert!(program.send(actor2arr(&module), governance).main_failed());
+
+    let transaction_id = submit_transaction(&program, &owners[0], &Action::DisableModule{module}.encode());
+    confirm_transaction(&program, &owners[1], &transaction_id);
+    assert!(get_modules(&program, None, 10).0 == modules[1..].to_vec());
+    assert!(program.send_bytes(actor2arr(&module), call).main_failed());
+}
+
+#[test]
+fn child_wallets() {
+    let sys = System::new();
+    init(&sys);
+    let board = Program::from_file(&sys, WASM_PATH);
+    let department = Program::from_file(&sys, WASM_PATH);
+    let board_id = program_actor(&board);
+    let department_id = program_actor(&department);
+    let (owner1, owner2, owner3) = (create_owner(1), create_owner(2), create_owner(3));
+    send_init(&vec![owner1, owner2], &board);
+    send_init(&vec![board_id, owner3], &department);
+    let child_id = submit_transaction(&department, &owner3, &Action::ChangeRequirement{required:1}.encode());
+    let res = board.send(actor2arr(&owner1), Action::ConfirmOnWallet{wallet:department_id, transaction_id:child_id});
+    assert!(res.contains(&Log::builder().payload(Event::Submission{transaction_id:0, details:ProposalDetails::default()})));
+    assert!(get_transaction(&board, &0).wallet_vote);
+
+    // a proposed vote doesn't count yet
+    let transaction_id = submit_transaction(&board, &owner1, &Action::RemoveChildWallet{wallet:department_id}.encode());
+    assert!(!get_transaction(&board, &transaction_id).wallet_vote);
+    let res = board.send(actor2arr(&owner2), Action::ConfirmTransaction{transaction_id, comment:None});
+    assert!(!res.main_failed() && res.others_failed());
+    assert!(get_transaction(&board, &transaction_id).failure_count == 1);
+
+    confirm_transaction(&board, &owner2, &0);
+    assert!(get_transaction(&department, &child_id).status == TransactionStatus::Executed);
+    let add = Action::AddOwner(department_id).encode();
+    let transaction_id = submit_transaction(&board, &owner1, &add);
+    let res = board.send(actor2arr(&owner2), Action::ConfirmTransaction{transaction_id, comment:None});
+    assert!(!res.main_failed() && res.others_failed());
+
+    // forgotten by governance, the department may own the board
+    let transaction_id = submit_transaction(&board, &owner1, &Action::RemoveChildWallet{wallet:department_id}.encode());
+    confirm_transaction(&board, &owner2, &transaction_id);
+    assert!(get_transaction(&board, &transaction_id).status == TransactionStatus::Executed);
+    let transaction_id = submit_transaction(&board, &owner1, &add);
+    confirm_transaction(&board, &owner2, &transaction_id);
+    assert!(get_transaction(&board, &transaction_id).status == TransactionStatus::Executed);
+}
+
+fn get_events(program:&Program, from_seq:u64)->Vec<LogEntry> {
+    query(program, Action::GetEvents{from_seq, limit:50}, |e|match e {
+        Event::GetEvents(e) => Some(e),
+        _ => None,
+    })
+}
+
+// Guard rejecting the calls with data `reject`, allowance spends of 13 and, after the call, the failed ones. It counts the checks.
+#[derive(Debug)]
+struct MockGuard {
+    checks:Rc<Cell<u32>>,
+}
+
+impl WasmProgram for MockGuard {
+    fn init(&mut self, _payload:Vec<u8>) -> Result<Option<Vec<u8>>, &'static str> {
+        Ok(None)
+    }
+
+    fn handle(&mut self, payload:Vec<u8>) -> Result<Option<Vec<u8>>, &'static str> {
+        self.checks.set(self.checks.get() + 1);
+        let reply = match GuardAction::decode(&mut payload.as_ref()).map_err(|_|"not a guard action")? {
+            GuardAction::CheckTransaction{data, ..} | GuardAction::CheckModuleCall{data, ..} if data == b"reject" => GuardReply::Rejected{reason:"rejected".into()},
+            GuardAction::CheckAllowanceSpend{value:13, ..} => GuardReply::Rejected{reason:"rejected".into()},
+            GuardAction::CheckAfterExecution{success:false, ..} => GuardReply::Rejected{reason:"failed".into()},
+            _ => GuardReply::Approved,
+        };
+        Ok(Some(reply.encode()))
+    }
+
+    fn handle_reply(&mut self, _payload:Vec<u8>) -> Result<(), &'static str> {
+        Ok(())
+    }
+}
+
+#[test]
+fn guard() {
+    let sys = System::new();
+    let (program, owners) = setup(&sys);
+    // a second wallet answers the guarded calls
+    let other = Program::from_file(&sys, WASM_PATH);
+    send_init(&owners, &other);
+    let checks = Rc::new(Cell::new(0));
+    let guard = Program::mock(&sys, MockGuard{checks:checks.clone()});
+    assert!(!guard.send_bytes(FROM_ID, b"init").main_failed());
+    let other_id = program_actor(&other);
+
+    let action = Action::SetGuard{guard:Some(program_actor(&guard))};
+    let transaction_id = submit_transaction(&program, &owners[0], &action.encode());
+    confirm_transaction(&program, &owners[1], &transaction_id);
+
+    // governance isn't checked
+    let transaction_id = submit_transaction(&program, &owners[0], &Action::ChangeMaxRetries{max_retries:5}.encode());
+    confirm_transaction(&program, &owners[1], &transaction_id);
+    assert!(get_transaction(&program, &transaction_id).status == TransactionStatus::Executed);
+    assert!(checks.get() == 0);
+
+    // approved before and after the call
+    let transaction_id = submit_transaction_to(&program, &owners[0], &other_id, &Action::GetOwners.encode());
+    confirm_transaction(&program, &owners[1], &transaction_id);
+    assert!(get_transaction(&program, &transaction_id).status == TransactionStatus::Executed);
+    assert!(checks.get() == 2);
+
+    // rejected before the call, the transaction stays pending
+    let transaction_id = submit_transaction_to(&program, &owners[0], &other_id, b"reject");
+    confirm_transaction(&program, &owners[1], &transaction_id);
+    let info = get_transaction(&program, &transaction_id);
+    assert!(info.status == TransactionStatus::Pending);
+    assert!(info.failure_count == 0 && info.last_failure == Some("guard: rejected".into()));
+    assert!(checks.get() == 3);
+    let rejected = |e:&LogEntry, id:uint, why:&str|matches!(&e.event, Event::GuardRejection{transaction_id, reason} if *transaction_id == id && reason == why);
+    assert!(get_events(&program, 0).iter().any(|e|rejected(e, transaction_id, "guard: rejected")));
+
+    // rejected after a failed call, the rejection is only recorded
+    let transaction_id = submit_transaction_to(&program, &owners[0], &other_id, b"garbage");
+    let res = program.send(actor2arr(&owners[1]), Action::ConfirmTransaction{transaction_id, comment:None});
+    assert!(!res.main_failed() && res.others_failed());
+    let info = get_transaction(&program, &transaction_id);
+    assert!(info.status == TransactionStatus::Pending && info.failure_count == 1);
+    assert!(checks.get() == 5);
+    assert!(get_events(&program, 0).iter().any(|e|rejected(e, transaction_id, "guard: failed")));
+
+    // allowance spends are checked too, a rejected one doesn't use up the allowance
+    let recipient = create_owner(7);
+    sys.mint_to(actor2arr(&owners[0]), 1_000);
+    assert!(!program.send_with_value(actor2arr(&owners[0]), Action::GetOwners, 1_000).main_failed());
+    let action = Action::SetAllowance{owner:owners[2], amount:100, period:10, destination:None};
+    let transaction_id = submit_transaction(&program, &owners[0], &action.encode());
+    confirm_transaction(&program, &owners[1], &transaction_id);
+    let res = program.send(actor2arr(&owners[2]), Action::SpendAllowance{to:recipient, value:13});
+    let rejection = Event::AllowanceSpendRejection{owner:owners[2], to:recipient, value:13, reason:"guard: rejected".into()};
+    assert!(res.contains(&Log::builder().dest(actor2arr(&owners[2])).payload(rejection)));
+    assert!(get_allowance(&program, &owners[2]).unwrap().remaining == 100);
+    let res = program.send(actor2arr(&owners[2]), Action::SpendAllowance{to:recipient, value:60});
+    let spent = Event::AllowanceSpent{owner:owners[2], to:recipient, value:60, remaining:40};
+    assert!(res.contains(&Log::builder().dest(actor2arr(&owners[2])).payload(spent)));
+    assert!(res.contains(&Log::builder().dest(actor2arr(&recipient))));
+    assert!(checks.get() == 7);
+}
+
+fn get_allowance(program:&Program, owner:&ActorId)->Option<AllowanceInfo> {
+    query(program, Action::GetAllowance{owner:*owner}, |e|match e {
+        Event::GetAllowance(allowance) => Some(allowance),
+        _ => None,
+    })
+}
+
+#[test]
+fn allowances() {
+    let sys = System::new();
+    let (program, owners) = setup(&sys);
+    let (recipient, outsider) = (create_owner(7), create_owner(8));
+    // the value of any message stays with the wallet
+    sys.mint_to(actor2arr(&owners[0]), 10_000);
+    assert!(!program.send_with_value(actor2arr(&owners[0]), Action::GetOwners, 10_000).main_failed());
+
+    let action = Action::SetAllowance{owner:owners[2], amount:1000, period:10, destination:Some(recipient)};
+    let transaction_id = submit_transaction(&program, &owners[0], &action.encode());
+    confirm_transaction(&program, &owners[1], &transaction_id);
+    let allowance = get_allowance(&program, &owners[2]).unwrap();
+    assert!(allowance.amount == 1000 && allowance.period == 10 && allowance.destination == Some(recipient));
+    assert!(allowance.remaining == 1000);
+    assert!(get_allowance(&program, &owners[3]).is_none());
+
+    // spent alone, within the limit and only to the destination
+    let res = program.send(actor2arr(&owners[2]), Action::SpendAllowance{to:recipient, value:600});
+    assert!(res.contains(&Log::builder().payload(Event::AllowanceSpent{owner:owners[2], to:recipient, value:600, remaining:400})));
+    assert!(res.contains(&Log::builder().dest(actor2arr(&recipient))));
+    assert!(get_allowance(&program, &owners[2]).unwrap().remaining == 400);
+    assert!(program.send(actor2arr(&owners[2]), Action::SpendAllowance{to:recipient, value:500}).main_failed());
+    assert!(program.send(actor2arr(&owners[2]), Action::SpendAllowance{to:outsider, value:100}).main_failed());
+    assert!(program.send(actor2arr(&owners[3]), Action::SpendAllowance{to:recipient, value:1}).main_failed());
+
+    // a new period resets it
+    sys.spend_blocks(10);
+    assert!(get_allowance(&program, &owners[2]).unwrap().remaining == 1000);
+    let res = program.send(actor2arr(&owners[2]), Action::SpendAllowance{to:recipient, value:1000});
+    assert!(res.contains(&Log::builder().payload(Event::AllowanceSpent{owner:owners[2], to:recipient, value:1000, remaining:0})));
+
+    // it goes with the owner
+    let transaction_id = submit_transaction(&program, &owners[0], &Action::RemoveOwner(owners[2]).encode());
+    confirm_transaction(&program, &owners[1], &transaction_id);
+    assert!(get_allowance(&program, &owners[2]).is_none());
+    sys.spend_blocks(10);
+    assert!(program.send(actor2arr(&owners[2]), Action::SpendAllowance{to:recipient, value:1}).main_failed());
+}
+
+fn get_gas_reservations(program:&Program)->(u64, Vec<GasReservationInfo>) {
+    query(program, Action::GetGasReservations, |e|match e {
+        Event::GetGasReservations{total, reservations} => Some((total, reservations)),
+        _ => None,
+    })
+}
+
+#[test]
+fn gas_reservations() {
+    let sys = System::new();
+    let (program, owners) = setup(&sys);
+    let amount = 5_000_000_000;
+
+    assert!(program.send(actor2arr(&create_owner(8)), Action::ReserveGas{amount, duration:20}).main_failed());
+    // too little to even handle the scheduled message
+    assert!(program.send(actor2arr(&owners[0]), Action::ReserveGas{amount:SCHEDULED_EXECUTION_GAS - 1, duration:20}).main_failed());
+    let res = program.send(actor2arr(&owners[0]), Action::ReserveGas{amount, duration:20});
+    assert!(res.contains(&Log::builder().payload(Event::GasReservation{sender:owners[0], amount, duration:20})));
+    let (total, reservations) = get_gas_reservations(&program);
+    assert!(total == amount && reservations.len() == 1);
+
+    // the confirmation schedules the execution, paid from the pool
+    let transaction_id = submit_transaction(&program, &owners[0], &Action::ChangeMaxRetries{max_retries:5}.encode());
+    let e = confirm_transaction(&program, &owners[1], &transaction_id);
+    assert!(matches!(e, Event::Confirmation{dispatched:false, ..}));
+    assert!(get_transaction(&program, &transaction_id).status == TransactionStatus::Executed);
+    assert!(get_gas_reservations(&program).1.is_empty());
+
+    // a gas limit the pool can't cover is executed by the confirmation
+    let res = program.send(actor2arr(&owners[0]), Action::ReserveGas{amount, duration:20});
+    assert!(!res.main_failed());
+    let action = Action::SubmitTransaction{destination:program_actor(&program), value:0, data:Action::ReleaseGas.encode(), gas_limit:Some(amount), details:ProposalDetails::default(), depends_on:vec![], nonce:None, condition:None};
+    let res = program.send(actor2arr(&owners[0]), action);
+    assert!(res.contains(&Log::builder().payload(Event::Submission{transaction_id:1, details:ProposalDetails::default()})));
+    let e = confirm_transaction(&program, &owners[1], &1);
+    assert!(matches!(e, Event::Confirmation{dispatched:true, ..}));
+    assert!(get_transaction(&program, &1).status == TransactionStatus::Executed);
+    assert!(get_gas_reservations(&program).1.is_empty());
+
+    // reservations expire
+    let res = program.send(actor2arr(&owners[0]), Action::ReserveGas{amount, duration:5});
+    assert!(!res.main_failed());
+    assert!(get_gas_reservations(&program).1.len() == 1);
+    sys.spend_blocks(5);
+    assert!(get_gas_reservations(&program).1.is_empty());
+}
+
+#[test]
+fn retries() {
+    let sys = System::new();
+    let (program, owners) = setup(&sys);
+    // a second wallet fails on a payload it can't decode
+    let other = Program::from_file(&sys, WASM_PATH);
+    send_init(&owners, &other);
+
+    let transaction_id = submit_transaction(&program, &owners[0], &Action::ChangeMaxRetries{max_retries:1}.encode());
+    confirm_transaction(&program, &owners[1], &transaction_id);
+    assert!(get_transaction(&program, &transaction_id).status == TransactionStatus::Executed);
+
+    let transaction_id = submit_transaction_to(&program, &owners[0], &program_actor(&other), b"garbage");
+    let res = program.send(actor2arr(&owners[0]), Action::RetryTransaction{transaction_id});
+    assert!(res.main_failed()); // it has not failed yet
+    let res = program.send(actor2arr(&owners[1]), Action::ConfirmTransaction{transaction_id, comment:None});
+    assert!(!res.main_failed() && res.others_failed());
+    let info = get_transaction(&program, &transaction_id);
+    assert!(info.status == TransactionStatus::Pending && info.failure_count == 1);
+
+    assert!(program.send(actor2arr(&create_owner(8)), Action::RetryTransaction{transaction_id}).main_failed());
+    let res = program.send(actor2arr(&owners[2]), Action::RetryTransaction{transaction_id});
+    assert!(!res.main_failed());
+    assert!(get_transaction(&program, &transaction_id).failure_count == 2);
+
+    // the limit stops the attempts, not the confirmations
+    assert!(program.send(actor2arr(&owners[2]), Action::RetryTransaction{transaction_id}).main_failed());
+    assert!(program.send(actor2arr(&owners[2]), Action::ExecuteTransaction{transaction_id}).main_failed());
+    let e = confirm_transaction(&program, &owners[2], &transaction_id);
+    assert!(matches!(e, Event::Confirmation{dispatched:false, ..}));
+    assert!(3 == get_confirmation_count(&program, &transaction_id));
+    assert!(get_transaction(&program, &transaction_id).failure_count == 2);
+}
+
+#[test]
+fn gas_limit() {
+    let sys = System::new();
+    let (program, owners) = setup(&sys);
+    // a second wallet answers the call
+    let other = Program::from_file(&sys, WASM_PATH);
+    send_init(&owners, &other);
+
+    // too little gas to be handled
+    let action = Action::SubmitTransaction{destination:program_actor(&other), value:0, data:Action::GetOwners.encode(), gas_limit:Some(1_000), details:ProposalDetails::default(), depends_on:vec![], nonce:None, condition:None};
+    let res = program.send(actor2arr(&owners[0]), action);
+    assert!(res.contains(&Log::builder().payload(Event::Submission{transaction_id:0, details:ProposalDetails::default()})));
+    assert!(get_transaction(&program, &0).gas_limit == Some(1_000));
+    let res = program.send(actor2arr(&owners[1]), Action::ConfirmTransaction{transaction_id:0, comment:None});
+    assert!(!res.main_failed() && res.others_failed());
+    let info = get_transaction(&program, &0);
+    assert!(info.status == TransactionStatus::Pending && info.failure_count == 1);
+
+    let gas_limit = Some(10_000_000_000);
+    let transaction_id = submit_transaction(&program, &owners[0], &Action::ChangeGasLimit{transaction_id:0, gas_limit}.encode());
+    confirm_transaction(&program, &owners[1], &transaction_id);
+    assert!(get_transaction(&program, &0).gas_limit == gas_limit);
+    assert!(!program.send(actor2arr(&owners[0]), Action::RetryTransaction{transaction_id:0}).main_failed());
+    assert!(get_transaction(&program, &0).status == TransactionStatus::Executed);
+
+    // an executed transaction keeps its gas limit
+    let transaction_id = submit_transaction(&program, &owners[0], &Action::ChangeGasLimit{transaction_id:0, gas_limit:None}.encode());
+    let res = program.send(actor2arr(&owners[1]), Action::ConfirmTransaction{transaction_id, comment:None});
+    assert!(!res.main_failed() && res.others_failed());
+    assert!(get_transaction(&program, &0).gas_limit == gas_limit);
+}
+
+#[test]
+fn bond_transfers() {
+    let sys = System::new();
+    let (program, owners) = setup(&sys);
+    let submitter = actor2arr(&owners[0]);
+    sys.mint_to(submitter, 10_000);
+
+    let transaction_id = submit_transaction(&program, &owners[0], &Action::ChangeBondConfig{bond:1000, ttl:0}.encode());
+    confirm_transaction(&program, &owners[1], &transaction_id);
+    let submit = |data:&[u8], value:uint| {
+        let action = Action::SubmitTransaction{destination:program_actor(&program), value:0, data:data.to_vec(), gas_limit:None, details:ProposalDetails::default(), depends_on:vec![], nonce:None, condition:None};
+        program.send_with_value(submitter, action, value)
+    };
+    let logged = |event:Event|get_events(&program, 0).iter().any(|e|e.event.encode() == event.encode());
+
+    // only the configured bond is held, the excess is a deposit
+    let res = submit(&Action::ChangeMaxRetries{max_retries:5}.encode(), 1500);
+    assert!(!res.main_failed());
+    assert!(get_transaction(&program, &1).bond == 1000);
+    assert!(logged(Event::Deposit{sender:owners[0], value:500}));
+
+    // refunded on execution
+    let res = program.send(actor2arr(&owners[1]), Action::ConfirmTransaction{transaction_id:1, comment:None});
+    assert!(res.contains(&Log::builder().dest(submitter)));
+    assert!(get_transaction(&program, &1).bond == 0);
+    assert!(logged(Event::BondRefund{transaction_id:1, submitter:owners[0], amount:1000}));
+
+    // and on withdrawal
+    assert!(!submit(&Action::ChangeMaxRetries{max_retries:6}.encode(), 1000).main_failed());
+    let res = program.send(submitter, Action::CancelTransaction{transaction_id:2});
+    assert!(res.contains(&Log::builder().dest(submitter)));
+    assert!(logged(Event::BondRefund{transaction_id:2, submitter:owners[0], amount:1000}));
+
+    // forfeited once the owners reject it
+    assert!(!submit(&Action::ChangeMaxRetries{max_retries:7}.encode(), 1000).main_failed());
+    for owner in &owners[1..] {
+        let res = program.send(actor2arr(owner), Action::RejectTransaction{transaction_id:3, comment:None});
+        assert!(!res.main_failed());
+        assert!(!res.contains(&Log::builder().dest(submitter)));
+    }
+    assert!(get_transaction(&program, &3).status == TransactionStatus::Cancelled);
+    assert!(get_transaction(&program, &3).bond == 0);
+    assert!(logged(Event::BondForfeit{transaction_id:3, amount:1000}));
+    assert!(program.send(submitter, Action::ClaimBond{transaction_id:3}).main_failed());
+}
+
+// Oracle answering any query with its current price.
+#[derive(Debug)]
+struct MockOracle {
+    price:Rc<Cell<uint>>,
+}
+
+impl WasmProgram for MockOracle {
+    fn init(&mut self, _payload:Vec<u8>) -> Result<Option<Vec<u8>>, &'static str> {
+        Ok(None)
+    }
+
+    fn handle(&mut self, _payload:Vec<u8>) -> Result<Option<Vec<u8>>, &'static str> {
+        Ok(Some(self.price.get().encode()))
+    }
+
+    fn handle_reply(&mut self, _payload:Vec<u8>) -> Result<(), &'static str> {
+        Ok(())
+    }
+}
+
+#[test]
+fn oracle_conditions() {
+    let sys = System::new();
+    let (program, owners) = setup(&sys);
+    let wallet = program_actor(&program);
+    let price = Rc::new(Cell::new(100));
+    let oracle = Program::mock(&sys, MockOracle{price:price.clone()});
+    assert!(!oracle.send_bytes(FROM_ID, b"init").main_failed());
+
+    let query = |comparator:Comparator, expected:uint|Some(Condition::Query{program:program_actor(&oracle), payload:b"price".to_vec(), comparator, expected:expected.encode()});
+    let cases = [
+        (Comparator::Equals, 100, None),
+        (Comparator::GreaterThan, 150, Some(200)),
+        (Comparator::LessThan, 150, Some(100)),
+    ];
+    for (i, (comparator, expected, change)) in cases.into_iter().enumerate() {
+        let data = Action::ChangeMaxRetries{max_retries:i as u32 + 5}.encode();
+        let transaction_id = submit_with(&program, &owners[0], &wallet, &data, vec![], None, query(comparator, expected));
+        confirm_transaction(&program, &owners[1], &transaction_id);
+        let Some(change) = change else {
+            assert!(get_transaction(&program, &transaction_id).status == TransactionStatus::Executed);
+            continue;
+        };
+        // not met yet, the transaction waits
+        let transaction = get_transaction(&program, &transaction_id);
+        assert!(transaction.status == TransactionStatus::Pending && transaction.failure_count == 0);
+        assert!(transaction.last_failure == Some("condition: query reply doesn't match".into()));
+        price.set(change);
+        assert!(!program.send(actor2arr(&owners[1]), Action::ExecuteTransaction{transaction_id}).main_failed());
+        assert!(get_transaction(&program, &transaction_id).status == TransactionStatus::Executed);
+    }
+}
+
+#[test]
+fn proposal_details() {
+    let sys = System::new();
+    init(&sys);
+    let program = Program::from_file(&sys, WASM_PATH);
+    let owners = (1..5).map(|x|create_owner(x)).collect::<Vec<_>>();
+    send_init_with(&owners, 3, &program);
+    let submit = |details:ProposalDetails| {
+        let data = Action::ChangeMaxRetries{max_retries:5}.encode();
+        program.send(actor2arr(&owners[0]), Action::SubmitTransaction{destination:program_actor(&program), value:0, data, gas_limit:None, details, depends_on:vec![], nonce:None, condition:None})
+    };
+
+    let details = ProposalDetails{title:Some("Title".into()), description:Some("Description".into()), content_hash:Some([7; 32]), uri:Some("ipfs://doc".into())};
+    assert!(!submit(details.clone()).main_failed());
+    assert!(get_transaction(&program, &0).details == details);
+    let long = |len:usize|Some("x".repeat(len));
+    for details in [
+        ProposalDetails{title:long(MAX_TITLE_LENGTH + 1), ..Default::default()},
+        ProposalDetails{description:long(MAX_DESCRIPTION_LENGTH + 1), ..Default::default()},
+        ProposalDetails{uri:long(MAX_URI_LENGTH + 1), ..Default::default()},
+    ] {
+        assert!(submit(details).main_failed());
+    }
+    let bounds = ProposalDetails{title:long(MAX_TITLE_LENGTH), description:long(MAX_DESCRIPTION_LENGTH), content_hash:None, uri:long(MAX_URI_LENGTH)};
+    assert!(!submit(bounds).main_failed());
+
+    // only the submitter amends, within the bound
+    let amend = |owner:&ActorId, transaction_id:uint, description:Option<String>|program.send(actor2arr(owner), Action::AmendDescription{transaction_id, description});
+    let res = amend(&owners[0], 0, Some("Amended".into()));
+    assert!(res.contains(&Log::builder().payload(Event::DescriptionAmendment{transaction_id:0})));
+    assert!(get_transaction(&program, &0).details.description == Some("Amended".into()));
+    assert!(get_transaction(&program, &0).details.title == details.title);
+    assert!(amend(&owners[1], 0, None).main_failed());
+    assert!(amend(&owners[0], 0, long(MAX_DESCRIPTION_LENGTH + 1)).main_failed());
+
+    // and only while nobody else confirmed
+    confirm_transaction(&program, &owners[1], &0);
+    assert!(amend(&owners[0], 0, None).main_failed());
+    assert!(!amend(&owners[0], 1, None).main_failed());
+
+    // nor once withdrawn
+    assert!(!program.send(actor2arr(&owners[0]), Action::CancelTransaction{transaction_id:1}).main_failed());
+    assert!(amend(&owners[0], 1, Some("Withdrawn".into())).main_failed());
+}